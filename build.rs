@@ -1,4 +1,24 @@
-const COMMANDS: &[&str] = &["ping"];
+const COMMANDS: &[&str] = &[
+  "ping",
+  "connect",
+  "close",
+  "close_all",
+  "execute",
+  "select",
+  "select_blob",
+  "execute_named",
+  "select_named",
+  "execute_batch",
+  "run_transaction",
+  "begin_transaction",
+  "execute_in_transaction",
+  "select_in_transaction",
+  "commit",
+  "rollback",
+  "savepoint",
+  "release_savepoint",
+  "rollback_to_savepoint",
+];
 
 fn main() {
   tauri_plugin::Builder::new(COMMANDS)
@@ -1,5 +1,90 @@
-import { invoke } from '@tauri-apps/api/core'
+import { invoke as tauriInvoke, type InvokeArgs } from '@tauri-apps/api/core'
 
+export type SqlErrorKind =
+  | 'databaseNotLoaded'
+  | 'pathNotAllowed'
+  | 'notInScope'
+  | 'readOnly'
+  | 'queryNotFound'
+  | 'databaseInUse'
+  | 'aliasInUse'
+  | 'databaseNotOwned'
+  | 'transactionNotFound'
+  | 'transactionFinished'
+  | 'transactionExpired'
+  | 'transactionNotOwned'
+  | 'savepointNotFound'
+  | 'invalidSavepointName'
+  | 'unsupportedOption'
+  | 'unsupportedDatatype'
+  | 'invalidBindValue'
+  | 'unsupportedTypeHint'
+  | 'invalidRequest'
+  | 'uniqueViolation'
+  | 'foreignKeyViolation'
+  | 'notNullViolation'
+  | 'checkViolation'
+  | 'syntaxError'
+  | 'busy'
+  | 'serializationFailure'
+  | 'database'
+  | 'rowNotFound'
+  | 'decode'
+  | 'connection'
+  | 'configuration'
+  | 'json'
+  | 'io'
+  | 'other'
+
+export interface SqlErrorDetails {
+  kind: SqlErrorKind
+  message: string
+  /** SQLSTATE for Postgres and MySQL, the extended result code for SQLite. */
+  code: string | null
+  constraint: string | null
+  table: string | null
+  column: string | null
+  /** Whether running the same operation again may succeed, e.g. after a deadlock or busy database. */
+  retryable: boolean
+  /** Index of the failing statement or step of `executeBatch` / `runTransaction`. */
+  index: number | null
+}
+
+/** Every function in this module rejects with a `SqlError` when the plugin reports an error. */
+export class SqlError extends Error implements SqlErrorDetails {
+  readonly kind: SqlErrorKind
+  readonly code: string | null
+  readonly constraint: string | null
+  readonly table: string | null
+  readonly column: string | null
+  readonly retryable: boolean
+  readonly index: number | null
+
+  constructor(details: SqlErrorDetails) {
+    super(details.message)
+    this.name = 'SqlError'
+    this.kind = details.kind
+    this.code = details.code
+    this.constraint = details.constraint
+    this.table = details.table
+    this.column = details.column
+    this.retryable = details.retryable
+    this.index = details.index
+  }
+}
+
+async function invoke<T>(cmd: string, args: InvokeArgs): Promise<T> {
+  try {
+    return await tauriInvoke<T>(cmd, args)
+  } catch (err) {
+    if (typeof err === 'object' && err !== null && 'kind' in err && 'message' in err) {
+      throw new SqlError(err as SqlErrorDetails)
+    }
+    throw err
+  }
+}
+
+/** Opaque name of a connected database; never the connection URL. */
 export type DbHandle = string
 
 export interface ExecuteResult {
@@ -8,13 +93,114 @@ export interface ExecuteResult {
 }
 
 export interface ExecuteOptions {
+  /** Bind values; a `bigint` is sent as a decimal string so no digit is lost. */
+  values?: unknown[]
+}
+
+/**
+ * How select results hold integers beyond `Number.MAX_SAFE_INTEGER`, MySQL unsigned BIGINTs
+ * and DECIMAL / NUMERIC values: `'lossless'` (default) returns them as strings,
+ * `'number'` rounds them to a JavaScript number.
+ */
+export type NumericPolicy = 'lossless' | 'number'
+
+export interface SelectOptions extends ExecuteOptions {
+  /** Overrides the database's numeric policy for this query. */
+  numeric?: NumericPolicy
+}
+
+export interface BatchStatement {
+  query: string
   values?: unknown[]
 }
 
+export type TransactionStep =
+  | { kind: 'execute'; query: string; values?: unknown[] }
+  | { kind: 'select'; query: string; values?: unknown[] }
+
+export type TransactionStepResult =
+  | ({ kind: 'execute' } & ExecuteResult)
+  | { kind: 'select'; rows: SelectRow[] }
+
+export type IsolationLevel = 'readUncommitted' | 'readCommitted' | 'repeatableRead' | 'serializable'
+
+export type SqliteLockMode = 'deferred' | 'immediate' | 'exclusive'
+
+export interface BeginOptions {
+  /** Server default when omitted. SQLite is always serializable. */
+  isolationLevel?: IsolationLevel
+  /** MySQL and Postgres only. */
+  readOnly?: boolean
+  /** Postgres only. */
+  deferrable?: boolean
+  /** SQLite only; `'deferred'` when omitted. */
+  sqliteLockMode?: SqliteLockMode
+  /** Roll back after this many idle milliseconds; `0` disables the plugin-wide default. */
+  idleTimeoutMs?: number
+}
+
 export interface SelectRow {
   [key: string]: unknown
 }
 
+export type TypeHint =
+  | 'text'
+  | 'int'
+  | 'bigint'
+  | 'float'
+  | 'bool'
+  | 'json'
+  | 'jsonb'
+  | 'uuid'
+  | 'date'
+  | 'time'
+  | 'timestamp'
+  | 'timestamptz'
+  | 'bytea'
+  | 'blob'
+  | 'text[]'
+  | 'int[]'
+  | 'bigint[]'
+  | 'float[]'
+  | 'bool[]'
+  | 'uuid[]'
+
+export interface TypedValue {
+  type: TypeHint
+  value: unknown
+}
+
+/**
+ * Bind value with an explicit database type, e.g. `typed('uuid', id)`,
+ * `typed('timestamptz', date.toISOString())` or `typed('int[]', [1, 2])`.
+ * `null` binds a NULL of that type.
+ */
+export function typed(type: TypeHint, value: unknown): TypedValue {
+  const encode = (v: unknown) => (typeof v === 'bigint' ? v.toString() : v)
+  if (value instanceof Uint8Array || value instanceof ArrayBuffer) {
+    return { type, value: toBase64(value) }
+  }
+  return { type, value: Array.isArray(value) ? value.map(encode) : encode(value) }
+}
+
+function toBase64(data: Uint8Array | ArrayBuffer): string {
+  const bytes = data instanceof Uint8Array ? data : new Uint8Array(data)
+  let binary = ''
+  // Chunked so large blobs do not exceed the argument limit of fromCharCode.
+  for (let i = 0; i < bytes.length; i += 0x8000) {
+    binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000))
+  }
+  return btoa(binary)
+}
+
+function encodeValues(values: unknown[] = []): unknown[] {
+  return values.map((v) => {
+    if (typeof v === 'bigint') return { type: 'bigint', value: v.toString() }
+    if (v instanceof Uint8Array || v instanceof ArrayBuffer) return { type: 'blob', value: toBase64(v) }
+    return v
+  })
+}
+
 export async function ping(value?: string): Promise<string | null> {
   const res = await invoke<{ value?: string }>('plugin:sql-transaction|ping', {
     payload: { value },
@@ -22,13 +208,75 @@ export async function ping(value?: string): Promise<string | null> {
   return res.value ?? null
 }
 
-export async function connect(url: string): Promise<DbHandle> {
+export interface PoolOptions {
+  maxConnections?: number
+  minConnections?: number
+  /** How long to wait for a free connection before failing. */
+  acquireTimeoutMs?: number
+  /** Close connections idle for this long; `0` keeps them open. */
+  idleTimeoutMs?: number
+  /** Replace connections once they are this old; `0` keeps them indefinitely. */
+  maxLifetimeMs?: number
+  /** Ping a connection before handing it out. */
+  testBeforeAcquire?: boolean
+}
+
+export type SqliteJournalMode = 'delete' | 'truncate' | 'persist' | 'memory' | 'wal' | 'off'
+export type SqliteSynchronous = 'off' | 'normal' | 'full' | 'extra'
+export type SqliteBaseDir = 'config' | 'data' | 'localData' | 'cache' | 'absoluteOnly'
+
+/** Applied to every connection of a SQLite pool. */
+export interface SqliteOptions {
+  /** Directory a relative path is resolved against; the plugin config default (or `config`) when omitted. */
+  baseDir?: SqliteBaseDir
+  journalMode?: SqliteJournalMode
+  synchronous?: SqliteSynchronous
+  foreignKeys?: boolean
+  busyTimeoutMs?: number
+  createIfMissing?: boolean
+  readOnly?: boolean
+  /** Extra `PRAGMA name = value` statements, e.g. `{ cache_size: '-4000' }`. */
+  pragmas?: Record<string, string>
+}
+
+export interface ConnectOptions {
+  /** Name to register the database under instead of a generated handle; it must not already be loaded. */
+  alias?: string
+  pool?: PoolOptions
+  /** Only valid for `sqlite:` URLs. */
+  sqlite?: SqliteOptions
+  /** Default numeric policy for selects on this database. */
+  numeric?: NumericPolicy
+  /** Reject writes through this handle and open its connections read-only. */
+  readOnly?: boolean
+}
+
+export async function connect(url: string, options: ConnectOptions = {}): Promise<DbHandle> {
   const res = await invoke<{ handle: string }>('plugin:sql-transaction|connect', {
-    payload: { url },
+    payload: { url, ...options },
   })
   return res.handle
 }
 
+export interface CloseOptions {
+  /** Roll back this webview's transactions still open on the database instead of rejecting. */
+  force?: boolean
+}
+
+/** Close the database's connection pool and release its handle. Only the webview that connected it can close it. */
+export async function close(db: DbHandle, options: CloseOptions = {}): Promise<void> {
+  await invoke('plugin:sql-transaction|close', {
+    payload: { db, force: options.force ?? false },
+  })
+}
+
+/** Close every database this webview connected. */
+export async function closeAll(options: CloseOptions = {}): Promise<void> {
+  await invoke('plugin:sql-transaction|close_all', {
+    payload: { force: options.force ?? false },
+  })
+}
+
 export async function execute(
   db: DbHandle,
   query: string,
@@ -37,7 +285,7 @@ export async function execute(
   const res = await invoke<{ rowsAffected: number; lastInsertId?: string }>(
     'plugin:sql-transaction|execute',
     {
-      payload: { db, query, values: options.values ?? [] },
+      payload: { db, query, values: encodeValues(options.values) },
     },
   )
   return { rowsAffected: res.rowsAffected, lastInsertId: res.lastInsertId ?? null }
@@ -46,14 +294,99 @@ export async function execute(
 export async function select<T extends SelectRow = SelectRow>(
   db: DbHandle,
   query: string,
-  options: ExecuteOptions = {},
+  options: SelectOptions = {},
 ): Promise<T[]> {
   const res = await invoke<{ rows: T[] }>('plugin:sql-transaction|select', {
-    payload: { db, query, values: options.values ?? [] },
+    payload: { db, query, values: encodeValues(options.values), numeric: options.numeric },
+  })
+  return res.rows
+}
+
+/** Run a statement registered on the Rust side under `name`, binding only `values`. */
+export async function executeNamed(
+  db: DbHandle,
+  name: string,
+  options: ExecuteOptions = {},
+): Promise<ExecuteResult> {
+  const res = await invoke<{ rowsAffected: number; lastInsertId?: string }>(
+    'plugin:sql-transaction|execute_named',
+    {
+      payload: { db, name, values: encodeValues(options.values) },
+    },
+  )
+  return { rowsAffected: res.rowsAffected, lastInsertId: res.lastInsertId ?? null }
+}
+
+/** Run a select registered on the Rust side under `name`, binding only `values`. */
+export async function selectNamed<T extends SelectRow = SelectRow>(
+  db: DbHandle,
+  name: string,
+  options: SelectOptions = {},
+): Promise<T[]> {
+  const res = await invoke<{ rows: T[] }>('plugin:sql-transaction|select_named', {
+    payload: { db, name, values: encodeValues(options.values), numeric: options.numeric },
   })
   return res.rows
 }
 
+/**
+ * Read the first column of the first row as bytes, transferred without JSON encoding.
+ * Suited to large blobs such as images or attachments; NULL yields an empty array.
+ */
+export async function selectBlob(
+  db: DbHandle,
+  query: string,
+  options: ExecuteOptions = {},
+): Promise<Uint8Array> {
+  const res = await invoke<ArrayBuffer>('plugin:sql-transaction|select_blob', {
+    payload: { db, query, values: encodeValues(options.values) },
+  })
+  return new Uint8Array(res)
+}
+
+/**
+ * Run many statements in one IPC call and one implicit transaction.
+ * Nothing is applied if any statement fails; the error message names its index.
+ */
+export async function executeBatch(
+  db: DbHandle,
+  statements: BatchStatement[],
+): Promise<ExecuteResult[]> {
+  const res = await invoke<{ results: { rowsAffected: number; lastInsertId?: string }[] }>(
+    'plugin:sql-transaction|execute_batch',
+    {
+      payload: { db, statements: statements.map((s) => ({ ...s, values: encodeValues(s.values) })) },
+    },
+  )
+  return res.results.map((r) => ({ rowsAffected: r.rowsAffected, lastInsertId: r.lastInsertId ?? null }))
+}
+
+/**
+ * Bind value referring to a field of an earlier step's result in `runTransaction`,
+ * e.g. `ref(0, 'lastInsertId')` or `ref(1, 'rows/0/id')`.
+ */
+export function ref(step: number, path?: string): { $ref: string } {
+  return { $ref: path ? `/${step}/${path}` : `/${step}` }
+}
+
+/**
+ * Run all steps in a single transaction on the backend, in one IPC call.
+ * Commits if every step succeeds, otherwise rolls back and rejects with the failing step's index.
+ */
+export async function runTransaction(
+  db: DbHandle,
+  steps: TransactionStep[],
+  options: BeginOptions = {},
+): Promise<TransactionStepResult[]> {
+  const res = await invoke<{ results: TransactionStepResult[] }>(
+    'plugin:sql-transaction|run_transaction',
+    {
+      payload: { db, ...options, steps: steps.map((s) => ({ ...s, values: encodeValues(s.values) })) },
+    },
+  )
+  return res.results
+}
+
 export class Transaction {
   constructor(private readonly txId: string) {}
 
@@ -61,12 +394,63 @@ export class Transaction {
     const res = await invoke<{ rowsAffected: number; lastInsertId?: string }>(
       'plugin:sql-transaction|execute_in_transaction',
       {
-        payload: { txId: this.txId, query, values: options.values ?? [] },
+        payload: { txId: this.txId, query, values: encodeValues(options.values) },
       },
     )
     return { rowsAffected: res.rowsAffected, lastInsertId: res.lastInsertId ?? null }
   }
 
+  /** Run many statements in one IPC call; a failing batch is undone without ending the transaction. */
+  async executeBatch(statements: BatchStatement[]): Promise<ExecuteResult[]> {
+    const res = await invoke<{ results: { rowsAffected: number; lastInsertId?: string }[] }>(
+      'plugin:sql-transaction|execute_batch',
+      {
+        payload: {
+          txId: this.txId,
+          statements: statements.map((s) => ({ ...s, values: encodeValues(s.values) })),
+        },
+      },
+    )
+    return res.results.map((r) => ({ rowsAffected: r.rowsAffected, lastInsertId: r.lastInsertId ?? null }))
+  }
+
+  async select<T extends SelectRow = SelectRow>(
+    query: string,
+    options: SelectOptions = {},
+  ): Promise<T[]> {
+    const res = await invoke<{ rows: T[] }>('plugin:sql-transaction|select_in_transaction', {
+      payload: { txId: this.txId, query, values: encodeValues(options.values), numeric: options.numeric },
+    })
+    return res.rows
+  }
+
+  async executeNamed(name: string, options: ExecuteOptions = {}): Promise<ExecuteResult> {
+    const res = await invoke<{ rowsAffected: number; lastInsertId?: string }>(
+      'plugin:sql-transaction|execute_named',
+      {
+        payload: { txId: this.txId, name, values: encodeValues(options.values) },
+      },
+    )
+    return { rowsAffected: res.rowsAffected, lastInsertId: res.lastInsertId ?? null }
+  }
+
+  async selectNamed<T extends SelectRow = SelectRow>(
+    name: string,
+    options: SelectOptions = {},
+  ): Promise<T[]> {
+    const res = await invoke<{ rows: T[] }>('plugin:sql-transaction|select_named', {
+      payload: { txId: this.txId, name, values: encodeValues(options.values), numeric: options.numeric },
+    })
+    return res.rows
+  }
+
+  async selectBlob(query: string, options: ExecuteOptions = {}): Promise<Uint8Array> {
+    const res = await invoke<ArrayBuffer>('plugin:sql-transaction|select_blob', {
+      payload: { txId: this.txId, query, values: encodeValues(options.values) },
+    })
+    return new Uint8Array(res)
+  }
+
   async commit(): Promise<void> {
     await invoke('plugin:sql-transaction|commit', { payload: { txId: this.txId } })
   }
@@ -74,11 +458,27 @@ export class Transaction {
   async rollback(): Promise<void> {
     await invoke('plugin:sql-transaction|rollback', { payload: { txId: this.txId } })
   }
+
+  async savepoint(name: string): Promise<void> {
+    await invoke('plugin:sql-transaction|savepoint', { payload: { txId: this.txId, name } })
+  }
+
+  async releaseSavepoint(name: string): Promise<void> {
+    await invoke('plugin:sql-transaction|release_savepoint', {
+      payload: { txId: this.txId, name },
+    })
+  }
+
+  async rollbackToSavepoint(name: string): Promise<void> {
+    await invoke('plugin:sql-transaction|rollback_to_savepoint', {
+      payload: { txId: this.txId, name },
+    })
+  }
 }
 
-export async function begin(db: DbHandle): Promise<Transaction> {
+export async function begin(db: DbHandle, options: BeginOptions = {}): Promise<Transaction> {
   const res = await invoke<{ txId: string }>('plugin:sql-transaction|begin_transaction', {
-    payload: { db },
+    payload: { db, ...options },
   })
   return new Transaction(res.txId)
 }
@@ -89,8 +489,9 @@ export async function begin(db: DbHandle): Promise<Transaction> {
 export async function transaction<T>(
   db: DbHandle,
   fn: (tx: Transaction) => Promise<T>,
+  options: BeginOptions = {},
 ): Promise<T> {
-  const tx = await begin(db)
+  const tx = await begin(db, options)
   try {
     const result = await fn(tx)
     await tx.commit()
@@ -0,0 +1,309 @@
+//! Conversion of JSON bind values to driver types.
+//!
+//! Plain JSON values are bound by their shape: strings as text, integers as 64-bit integers,
+//! other numbers as doubles, booleans as booleans and `null` as NULL. A value of the form
+//! `{ "type": <hint>, "value": ... }` is bound as the hinted type instead, for parameters whose
+//! type cannot be told from JSON: UUIDs, timestamps, binary data, JSONB or Postgres arrays.
+//! A hinted `null` is a NULL of that type. Such an object whose `type` is not a known hint is
+//! rejected rather than stored as JSON, so a misspelled hint cannot go unnoticed; a document of
+//! that shape is bound with the `json` hint.
+
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::Value as JsonValue;
+use sqlx::{
+  mysql::{MySql, MySqlArguments},
+  postgres::{PgArguments, Postgres},
+  query::Query,
+  sqlite::{Sqlite, SqliteArguments},
+  types::{
+    time::{Date, OffsetDateTime, PrimitiveDateTime, Time},
+    Json, Uuid,
+  },
+};
+use time::{format_description::well_known::Rfc3339, macros::format_description};
+
+use crate::{Error, Result};
+
+type SqliteQuery<'q> = Query<'q, Sqlite, SqliteArguments<'q>>;
+type MySqlQuery<'q> = Query<'q, MySql, MySqlArguments>;
+type PgQuery<'q> = Query<'q, Postgres, PgArguments>;
+
+/// Every type hint the plugin understands; not every driver supports all of them.
+const TYPE_HINTS: &[&str] = &[
+  "text",
+  "int",
+  "bigint",
+  "float",
+  "bool",
+  "json",
+  "jsonb",
+  "uuid",
+  "date",
+  "time",
+  "timestamp",
+  "timestamptz",
+  "bytea",
+  "blob",
+  "text[]",
+  "int[]",
+  "bigint[]",
+  "float[]",
+  "bool[]",
+  "uuid[]",
+];
+
+pub(crate) fn bind_value_sqlite(query: SqliteQuery<'_>, value: JsonValue) -> Result<SqliteQuery<'_>> {
+  let Some((hint, value)) = type_hint(&value) else {
+    return Ok(if value.is_null() {
+      query.bind(None::<String>)
+    } else if let Some(s) = value.as_str() {
+      query.bind(s.to_owned())
+    } else if let Some(n) = value.as_i64() {
+      query.bind(n)
+    } else if let Some(n) = value.as_f64() {
+      query.bind(n)
+    } else if let Some(b) = value.as_bool() {
+      query.bind(b)
+    } else {
+      query.bind(value.to_string())
+    });
+  };
+  Ok(match hint {
+    "text" => query.bind(nullable(value, text)?),
+    "int" => query.bind(nullable(value, integer::<i32>)?),
+    "bigint" => query.bind(nullable(value, integer::<i64>)?),
+    "float" => query.bind(nullable(value, float)?),
+    "bool" => query.bind(nullable(value, boolean)?),
+    // SQLite's JSON functions work on JSON text.
+    "json" => query.bind(nullable(value, |v| Ok(v.to_string()))?),
+    // Stored as text, which is what SQLite applications conventionally use for UUIDs.
+    "uuid" => query.bind(nullable(value, uuid)?.map(|u| u.hyphenated().to_string())),
+    "date" => query.bind(nullable(value, date)?),
+    "time" => query.bind(nullable(value, time)?),
+    "timestamp" => query.bind(nullable(value, timestamp)?),
+    "timestamptz" => query.bind(nullable(value, timestamptz)?),
+    "bytea" | "blob" => query.bind(nullable(value, bytes)?),
+    _ => return Err(unsupported_hint(hint, "SQLite")),
+  })
+}
+
+pub(crate) fn bind_value_mysql(query: MySqlQuery<'_>, value: JsonValue) -> Result<MySqlQuery<'_>> {
+  let Some((hint, value)) = type_hint(&value) else {
+    return Ok(if value.is_null() {
+      query.bind(None::<String>)
+    } else if let Some(s) = value.as_str() {
+      query.bind(s.to_owned())
+    } else if let Some(n) = value.as_i64() {
+      query.bind(n)
+    } else if let Some(n) = value.as_u64() {
+      query.bind(n)
+    } else if let Some(n) = value.as_f64() {
+      query.bind(n)
+    } else if let Some(b) = value.as_bool() {
+      query.bind(b)
+    } else {
+      query.bind(value.to_string())
+    });
+  };
+  Ok(match hint {
+    "text" => query.bind(nullable(value, text)?),
+    "int" => query.bind(nullable(value, integer::<i32>)?),
+    // BIGINT UNSIGNED columns go up to u64::MAX.
+    "bigint" => match nullable(value, integer::<i64>) {
+      Ok(n) => query.bind(n),
+      Err(_) => query.bind(integer::<u64>(value)?),
+    },
+    "float" => query.bind(nullable(value, float)?),
+    "bool" => query.bind(nullable(value, boolean)?),
+    "json" => query.bind(nullable(value, |v| Ok(Json(v.clone())))?),
+    // MySQL has no UUID type; CHAR(36) columns hold the text form.
+    "uuid" => query.bind(nullable(value, uuid)?.map(|u| u.hyphenated().to_string())),
+    "date" => query.bind(nullable(value, date)?),
+    "time" => query.bind(nullable(value, time)?),
+    "timestamp" => query.bind(nullable(value, timestamp)?),
+    // Converted to UTC, as MySQL TIMESTAMP columns are.
+    "timestamptz" => query.bind(nullable(value, timestamptz)?),
+    "bytea" | "blob" => query.bind(nullable(value, bytes)?),
+    _ => return Err(unsupported_hint(hint, "MySQL")),
+  })
+}
+
+pub(crate) fn bind_value_postgres(query: PgQuery<'_>, value: JsonValue) -> Result<PgQuery<'_>> {
+  let Some((hint, value)) = type_hint(&value) else {
+    return Ok(if value.is_null() {
+      query.bind(None::<String>)
+    } else if let Some(s) = value.as_str() {
+      query.bind(s.to_owned())
+    } else if let Some(n) = value.as_i64() {
+      query.bind(n)
+    } else if let Some(n) = value.as_f64() {
+      query.bind(n)
+    } else if let Some(b) = value.as_bool() {
+      query.bind(b)
+    } else {
+      query.bind(value.to_string())
+    });
+  };
+  Ok(match hint {
+    "text" => query.bind(nullable(value, text)?),
+    "int" => query.bind(nullable(value, integer::<i32>)?),
+    "bigint" => query.bind(nullable(value, integer::<i64>)?),
+    "float" => query.bind(nullable(value, float)?),
+    "bool" => query.bind(nullable(value, boolean)?),
+    // Sent as JSONB, which Postgres also casts to JSON on assignment.
+    "json" | "jsonb" => query.bind(nullable(value, |v| Ok(Json(v.clone())))?),
+    "uuid" => query.bind(nullable(value, uuid)?),
+    "date" => query.bind(nullable(value, date)?),
+    "time" => query.bind(nullable(value, time)?),
+    "timestamp" => query.bind(nullable(value, timestamp)?),
+    "timestamptz" => query.bind(nullable(value, timestamptz)?),
+    "bytea" | "blob" => query.bind(nullable(value, bytes)?),
+    "text[]" => query.bind(nullable(value, |v| array(v, text))?),
+    "int[]" => query.bind(nullable(value, |v| array(v, integer::<i32>))?),
+    "bigint[]" => query.bind(nullable(value, |v| array(v, integer::<i64>))?),
+    "float[]" => query.bind(nullable(value, |v| array(v, float))?),
+    "bool[]" => query.bind(nullable(value, |v| array(v, boolean))?),
+    "uuid[]" => query.bind(nullable(value, |v| array(v, uuid))?),
+    _ => return Err(unsupported_hint(hint, "PostgreSQL")),
+  })
+}
+
+/// Splits a `{ "type": ..., "value": ... }` bind value into its type hint and value.
+fn type_hint(value: &JsonValue) -> Option<(&str, &JsonValue)> {
+  let object = value.as_object()?;
+  if object.len() != 2 {
+    return None;
+  }
+  Some((object.get("type")?.as_str()?, object.get("value")?))
+}
+
+fn unsupported_hint(hint: &str, driver: &'static str) -> Error {
+  if TYPE_HINTS.contains(&hint) {
+    Error::UnsupportedTypeHint {
+      hint: hint.to_string(),
+      driver,
+    }
+  } else {
+    Error::InvalidBindValue(format!("unknown type hint: {hint}"))
+  }
+}
+
+fn invalid(expected: &str, value: &JsonValue) -> Error {
+  Error::InvalidBindValue(format!("expected {expected}, got {value}"))
+}
+
+/// Parses a hinted value, mapping `null` to a NULL of the hinted type.
+fn nullable<T>(value: &JsonValue, parse: impl Fn(&JsonValue) -> Result<T>) -> Result<Option<T>> {
+  if value.is_null() {
+    Ok(None)
+  } else {
+    parse(value).map(Some)
+  }
+}
+
+fn array<T>(value: &JsonValue, parse: impl Fn(&JsonValue) -> Result<T>) -> Result<Vec<Option<T>>> {
+  value
+    .as_array()
+    .ok_or_else(|| invalid("an array", value))?
+    .iter()
+    .map(|element| nullable(element, &parse))
+    .collect()
+}
+
+fn text(value: &JsonValue) -> Result<String> {
+  value
+    .as_str()
+    .map(str::to_owned)
+    .ok_or_else(|| invalid("a string", value))
+}
+
+/// Reads an integer sent as a JSON number or a decimal string, so 64-bit values beyond
+/// `Number.MAX_SAFE_INTEGER` reach the database intact.
+fn integer<T: FromStr>(value: &JsonValue) -> Result<T> {
+  let text = match value {
+    JsonValue::String(s) => s.trim().to_string(),
+    JsonValue::Number(n) if n.is_i64() || n.is_u64() => n.to_string(),
+    _ => return Err(invalid("an integer or integer string", value)),
+  };
+  text
+    .parse()
+    .map_err(|_| Error::InvalidBindValue(format!("not a valid integer: {text}")))
+}
+
+fn float(value: &JsonValue) -> Result<f64> {
+  match value {
+    JsonValue::Number(n) => n.as_f64(),
+    // NaN and Infinity cannot be written as JSON numbers.
+    JsonValue::String(s) => s.trim().parse().ok(),
+    _ => None,
+  }
+  .ok_or_else(|| invalid("a number", value))
+}
+
+fn boolean(value: &JsonValue) -> Result<bool> {
+  value.as_bool().ok_or_else(|| invalid("a boolean", value))
+}
+
+fn uuid(value: &JsonValue) -> Result<Uuid> {
+  value
+    .as_str()
+    .and_then(|s| Uuid::parse_str(s).ok())
+    .ok_or_else(|| invalid("a UUID string", value))
+}
+
+fn date(value: &JsonValue) -> Result<Date> {
+  value
+    .as_str()
+    .and_then(|s| Date::parse(s, format_description!("[year]-[month]-[day]")).ok())
+    .ok_or_else(|| invalid("a date like 2024-02-29", value))
+}
+
+fn time(value: &JsonValue) -> Result<Time> {
+  value
+    .as_str()
+    .and_then(|s| {
+      Time::parse(
+        s,
+        format_description!("[hour]:[minute]:[second][optional [.[subsecond]]]"),
+      )
+      .ok()
+    })
+    .ok_or_else(|| invalid("a time like 07:05:03.25", value))
+}
+
+fn timestamp(value: &JsonValue) -> Result<PrimitiveDateTime> {
+  value
+    .as_str()
+    .and_then(|s| {
+      // Accept SQL's space separator as well as ISO-8601's `T`.
+      PrimitiveDateTime::parse(
+        &s.replacen(' ', "T", 1),
+        format_description!("[year]-[month]-[day]T[hour]:[minute]:[second][optional [.[subsecond]]]"),
+      )
+      .ok()
+    })
+    .ok_or_else(|| invalid("a timestamp like 2024-02-29T07:05:03", value))
+}
+
+fn timestamptz(value: &JsonValue) -> Result<OffsetDateTime> {
+  value
+    .as_str()
+    .and_then(|s| OffsetDateTime::parse(&s.replacen(' ', "T", 1), &Rfc3339).ok())
+    .ok_or_else(|| invalid("an RFC 3339 timestamp like 2024-02-29T07:05:03Z", value))
+}
+
+/// Binary data as a base64 string (what the guest API sends for a `Uint8Array`) or an array
+/// of byte values.
+fn bytes(value: &JsonValue) -> Result<Vec<u8>> {
+  match value {
+    JsonValue::String(s) => BASE64.decode(s).ok(),
+    JsonValue::Array(elements) => elements
+      .iter()
+      .map(|e| e.as_u64().and_then(|b| u8::try_from(b).ok()))
+      .collect(),
+    _ => None,
+  }
+  .ok_or_else(|| invalid("a base64 string or an array of byte values", value))
+}
@@ -1,6 +1,11 @@
-use tauri::{AppHandle, command, Runtime};
+use tauri::{
+    command,
+    ipc::{CommandScope, GlobalScope, Response},
+    AppHandle, Runtime, Webview,
+};
 
 use crate::models::*;
+use crate::scope::{ConnectScope, DatabaseScope};
 use crate::Result;
 use crate::SqlTransactionExt;
 
@@ -15,17 +20,43 @@ pub(crate) async fn ping<R: Runtime>(
 #[command]
 pub(crate) async fn connect<R: Runtime>(
     app: AppHandle<R>,
+    webview: Webview<R>,
+    command_scope: CommandScope<DatabaseScope>,
+    global_scope: GlobalScope<DatabaseScope>,
     payload: ConnectRequest,
 ) -> Result<ConnectResponse> {
-    app.sql_transaction().connect(payload).await
+    let scope = ConnectScope::new(&command_scope, &global_scope);
+    app.sql_transaction().connect_in_scope(payload, &scope, Some(&webview)).await
 }
 
+#[command]
+pub(crate) async fn close<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: CloseRequest,
+) -> Result<AckResponse> {
+    app.sql_transaction().close(payload, Some(&webview)).await
+}
+
+#[command]
+pub(crate) async fn close_all<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: CloseAllRequest,
+) -> Result<AckResponse> {
+    app.sql_transaction().close_all(payload, Some(&webview)).await
+}
+
+/// Write commands check the database against the caller's `connect` scope, so a window
+/// limited to reading cannot write through a handle opened elsewhere.
 #[command]
 pub(crate) async fn execute<R: Runtime>(
     app: AppHandle<R>,
+    webview: Webview<R>,
     payload: ExecuteRequest,
 ) -> Result<ExecuteResponse> {
-    app.sql_transaction().execute(payload).await
+    let scope = ConnectScope::of_webview(&webview)?;
+    app.sql_transaction().execute_in_scope(payload, &scope).await
 }
 
 #[command]
@@ -36,34 +67,124 @@ pub(crate) async fn select<R: Runtime>(
     app.sql_transaction().select(payload).await
 }
 
+#[command]
+pub(crate) async fn execute_named<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: ExecuteNamedRequest,
+) -> Result<ExecuteResponse> {
+    let scope = ConnectScope::of_webview(&webview)?;
+    app.sql_transaction().execute_named_in_scope(payload, Some(&webview), &scope).await
+}
+
+#[command]
+pub(crate) async fn select_named<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: SelectNamedRequest,
+) -> Result<SelectResponse> {
+    app.sql_transaction().select_named(payload, Some(&webview)).await
+}
+
+#[command]
+pub(crate) async fn execute_batch<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: ExecuteBatchRequest,
+) -> Result<ExecuteBatchResponse> {
+    let scope = ConnectScope::of_webview(&webview)?;
+    app.sql_transaction().execute_batch_in_scope(payload, Some(&webview), &scope).await
+}
+
+/// Returns the bytes as a raw IPC response, so large blobs are not inflated into JSON arrays.
+#[command]
+pub(crate) async fn select_blob<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: SelectBlobRequest,
+) -> Result<Response> {
+    app.sql_transaction().select_blob(payload, Some(&webview)).await.map(Response::new)
+}
+
+#[command]
+pub(crate) async fn run_transaction<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: RunTransactionRequest,
+) -> Result<RunTransactionResponse> {
+    let scope = ConnectScope::of_webview(&webview)?;
+    app.sql_transaction().run_transaction_in_scope(payload, &scope).await
+}
+
 #[command]
 pub(crate) async fn begin_transaction<R: Runtime>(
     app: AppHandle<R>,
+    webview: Webview<R>,
     payload: BeginTransactionRequest,
 ) -> Result<BeginTransactionResponse> {
-    app.sql_transaction().begin(payload).await
+    app.sql_transaction().begin(payload, Some(&webview)).await
 }
 
 #[command]
 pub(crate) async fn execute_in_transaction<R: Runtime>(
     app: AppHandle<R>,
+    webview: Webview<R>,
     payload: TransactionExecuteRequest,
 ) -> Result<ExecuteResponse> {
-    app.sql_transaction().execute_in_tx(payload).await
+    let scope = ConnectScope::of_webview(&webview)?;
+    app.sql_transaction().execute_in_tx_in_scope(payload, Some(&webview), &scope).await
+}
+
+#[command]
+pub(crate) async fn select_in_transaction<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: TransactionSelectRequest,
+) -> Result<SelectResponse> {
+    app.sql_transaction().select_in_tx(payload, Some(&webview)).await
 }
 
 #[command]
 pub(crate) async fn commit<R: Runtime>(
     app: AppHandle<R>,
+    webview: Webview<R>,
     payload: CommitRequest,
 ) -> Result<AckResponse> {
-    app.sql_transaction().commit(payload).await
+    app.sql_transaction().commit(payload, Some(&webview)).await
 }
 
 #[command]
 pub(crate) async fn rollback<R: Runtime>(
     app: AppHandle<R>,
+    webview: Webview<R>,
     payload: RollbackRequest,
 ) -> Result<AckResponse> {
-    app.sql_transaction().rollback(payload).await
+    app.sql_transaction().rollback(payload, Some(&webview)).await
+}
+
+#[command]
+pub(crate) async fn savepoint<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: SavepointRequest,
+) -> Result<AckResponse> {
+    app.sql_transaction().savepoint(payload, Some(&webview)).await
+}
+
+#[command]
+pub(crate) async fn release_savepoint<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: ReleaseSavepointRequest,
+) -> Result<AckResponse> {
+    app.sql_transaction().release_savepoint(payload, Some(&webview)).await
+}
+
+#[command]
+pub(crate) async fn rollback_to_savepoint<R: Runtime>(
+    app: AppHandle<R>,
+    webview: Webview<R>,
+    payload: RollbackToSavepointRequest,
+) -> Result<AckResponse> {
+    app.sql_transaction().rollback_to_savepoint(payload, Some(&webview)).await
 }
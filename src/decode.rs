@@ -0,0 +1,441 @@
+//! Conversion of result rows to JSON.
+//!
+//! Each column is decoded according to its type name rather than by trying Rust types until
+//! one fits, so every supported type has one well-defined JSON form:
+//!
+//! - dates, times and timestamps are ISO-8601 strings, intervals ISO-8601 durations
+//! - DECIMAL / NUMERIC values are strings with every digit preserved, and so are integers
+//!   beyond `Number.MAX_SAFE_INTEGER`, unless the [`NumericPolicy`] asks for plain numbers
+//! - JSON columns are embedded as JSON, UUIDs are strings
+//! - binary columns are arrays of byte values
+//! - any other type is the server's text form of the value when it was sent as text, and an
+//!   array of its raw bytes otherwise, so one unusual column never fails the whole query
+
+use std::fmt::Write;
+
+use indexmap::IndexMap;
+use serde_json::Value as JsonValue;
+use sqlx::{
+  mysql::{types::MySqlTime, MySqlRow},
+  postgres::{
+    types::{Oid, PgInterval, PgTimeTz},
+    PgRow, PgTypeKind, PgValueFormat, PgValueRef, Postgres,
+  },
+  sqlite::SqliteRow,
+  types::{
+    time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset},
+    Uuid,
+  },
+  Column, ColumnIndex, Database, Decode, Row, Type, TypeInfo, ValueRef,
+};
+
+use crate::{models::NumericPolicy, Error, Result};
+
+pub(crate) fn rows_to_json_sqlite(rows: Vec<SqliteRow>, numeric: NumericPolicy) -> Result<Vec<IndexMap<String, JsonValue>>> {
+  let mut result = Vec::new();
+  for row in rows {
+    let mut map = IndexMap::new();
+    for (i, col) in row.columns().iter().enumerate() {
+      let value = decode_sqlite_value(&row, i, numeric)?;
+      map.insert(col.name().to_string(), value);
+    }
+    result.push(map);
+  }
+  Ok(result)
+}
+
+pub(crate) fn rows_to_json_mysql(rows: Vec<MySqlRow>, numeric: NumericPolicy) -> Result<Vec<IndexMap<String, JsonValue>>> {
+  let mut result = Vec::new();
+  for row in rows {
+    let mut map = IndexMap::new();
+    for (i, col) in row.columns().iter().enumerate() {
+      let value = decode_mysql_value(&row, i, numeric)?;
+      map.insert(col.name().to_string(), value);
+    }
+    result.push(map);
+  }
+  Ok(result)
+}
+
+pub(crate) fn rows_to_json_postgres(rows: Vec<PgRow>, numeric: NumericPolicy) -> Result<Vec<IndexMap<String, JsonValue>>> {
+  let mut result = Vec::new();
+  for row in rows {
+    let mut map = IndexMap::new();
+    for (i, col) in row.columns().iter().enumerate() {
+      let value = decode_postgres_value(&row, i, numeric)?;
+      map.insert(col.name().to_string(), value);
+    }
+    result.push(map);
+  }
+  Ok(result)
+}
+
+/// The first column of a row as raw bytes, for blobs returned outside JSON. NULL is empty.
+pub(crate) fn row_to_blob<R>(row: &R) -> Result<Vec<u8>>
+where
+  R: Row,
+  usize: ColumnIndex<R>,
+  for<'r> Vec<u8>: Decode<'r, R::Database> + Type<R::Database>,
+{
+  Ok(row.try_get::<Option<Vec<u8>>, _>(0)?.unwrap_or_default())
+}
+
+fn decode_sqlite_value(row: &SqliteRow, idx: usize, numeric: NumericPolicy) -> Result<JsonValue> {
+  let raw = row.try_get_raw(idx)?;
+  if raw.is_null() {
+    return Ok(JsonValue::Null);
+  }
+
+  // SQLite stores DATE, DATETIME and friends in whatever storage class they were written
+  // with, so the value's storage class decides how it is read and the declared column type
+  // only tells booleans apart from other integers.
+  let declared = row.column(idx).type_info().name().to_string();
+  let storage = raw.type_info().name().to_string();
+  let value = match storage.as_str() {
+    "INTEGER" => {
+      let v: i64 = decode::<sqlx::Sqlite, _>(raw)?;
+      if declared == "BOOLEAN" {
+        JsonValue::Bool(v != 0)
+      } else {
+        int_json(v, numeric)
+      }
+    }
+    "REAL" => float_json(decode::<sqlx::Sqlite, _>(raw)?),
+    "TEXT" => JsonValue::String(decode::<sqlx::Sqlite, _>(raw)?),
+    // SQLite has no other storage classes.
+    _ => bytes_json(decode::<sqlx::Sqlite, _>(raw)?),
+  };
+  Ok(value)
+}
+
+fn decode_mysql_value(row: &MySqlRow, idx: usize, numeric: NumericPolicy) -> Result<JsonValue> {
+  let raw = row.try_get_raw(idx)?;
+  if raw.is_null() {
+    return Ok(JsonValue::Null);
+  }
+
+  let name = raw.type_info().name().to_string();
+  let value = match name.as_str() {
+    "NULL" => JsonValue::Null,
+    "BOOLEAN" => JsonValue::Bool(decode::<sqlx::MySql, _>(raw)?),
+    "TINYINT" | "SMALLINT" | "INT" | "MEDIUMINT" | "BIGINT" => {
+      int_json(decode::<sqlx::MySql, _>(raw)?, numeric)
+    }
+    "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "INT UNSIGNED" | "MEDIUMINT UNSIGNED" | "BIGINT UNSIGNED"
+    | "YEAR" | "BIT" => uint_json(decode::<sqlx::MySql, _>(raw)?, numeric),
+    "FLOAT" => float32_json(decode::<sqlx::MySql, _>(raw)?),
+    "DOUBLE" => float_json(decode::<sqlx::MySql, _>(raw)?),
+    // Sent as text by the server in both protocols, so every digit is kept.
+    "DECIMAL" => decimal_json(decode::<sqlx::MySql, _>(raw)?, numeric),
+    "DATE" => format_date(decode::<sqlx::MySql, _>(raw)?).into(),
+    "TIME" => format_mysql_time(decode::<sqlx::MySql, _>(raw)?).into(),
+    "DATETIME" => format_datetime(decode::<sqlx::MySql, _>(raw)?).into(),
+    "TIMESTAMP" => format_offset_datetime(decode::<sqlx::MySql, _>(raw)?).into(),
+    "JSON" => decode::<sqlx::MySql, JsonValue>(raw)?,
+    "CHAR" | "VARCHAR" | "TINYTEXT" | "TEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM" | "SET" => {
+      JsonValue::String(decode::<sqlx::MySql, _>(raw)?)
+    }
+    "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB" | "GEOMETRY" => {
+      bytes_json(decode::<sqlx::MySql, _>(raw)?)
+    }
+    _ => {
+      let bytes: Vec<u8> = decode::<sqlx::MySql, _>(raw)?;
+      match String::from_utf8(bytes) {
+        Ok(text) => JsonValue::String(text),
+        Err(err) => bytes_json(err.into_bytes()),
+      }
+    }
+  };
+  Ok(value)
+}
+
+fn decode_postgres_value(row: &PgRow, idx: usize, numeric: NumericPolicy) -> Result<JsonValue> {
+  let raw = row.try_get_raw(idx)?;
+  if raw.is_null() {
+    return Ok(JsonValue::Null);
+  }
+
+  let type_info = raw.type_info().into_owned();
+  let name = match type_info.kind() {
+    // Enum labels are sent as plain text.
+    PgTypeKind::Enum(_) => return Ok(JsonValue::String(decode::<Postgres, _>(raw)?)),
+    PgTypeKind::Domain(base) => base.name(),
+    _ => type_info.name(),
+  };
+  match name.strip_suffix("[]") {
+    Some(element) => decode_postgres_array(element, raw, numeric),
+    None => decode_postgres_scalar(name, raw, numeric),
+  }
+}
+
+fn decode_postgres_scalar(name: &str, raw: PgValueRef<'_>, numeric: NumericPolicy) -> Result<JsonValue> {
+  let value = match name {
+    "VOID" => JsonValue::Null,
+    "BOOL" => JsonValue::Bool(decode::<Postgres, _>(raw)?),
+    "INT2" => JsonValue::Number(decode::<Postgres, i16>(raw)?.into()),
+    "INT4" => JsonValue::Number(decode::<Postgres, i32>(raw)?.into()),
+    "INT8" => int_json(decode::<Postgres, _>(raw)?, numeric),
+    "OID" => JsonValue::Number(decode::<Postgres, Oid>(raw)?.0.into()),
+    "FLOAT4" => float32_json(decode::<Postgres, _>(raw)?),
+    "FLOAT8" => float_json(decode::<Postgres, _>(raw)?),
+    "NUMERIC" => numeric_json(&decode::<Postgres, Vec<u8>>(raw)?, numeric)?,
+    "\"CHAR\"" => JsonValue::String(char::from(decode::<Postgres, i8>(raw)? as u8).to_string()),
+    "TEXT" | "VARCHAR" | "CHAR" | "BPCHAR" | "NAME" | "CITEXT" | "XML" | "UNKNOWN" => {
+      JsonValue::String(decode::<Postgres, _>(raw)?)
+    }
+    "UUID" => decode::<Postgres, Uuid>(raw)?.to_string().into(),
+    "JSON" | "JSONB" => decode::<Postgres, JsonValue>(raw)?,
+    "BYTEA" => bytes_json(decode::<Postgres, _>(raw)?),
+    "DATE" => format_date(decode::<Postgres, _>(raw)?).into(),
+    "TIME" => format_time(decode::<Postgres, _>(raw)?).into(),
+    "TIMETZ" => {
+      let value: PgTimeTz<Time, UtcOffset> = decode::<Postgres, _>(raw)?;
+      format!("{}{}", format_time(value.time), format_offset(value.offset)).into()
+    }
+    "TIMESTAMP" => format_datetime(decode::<Postgres, _>(raw)?).into(),
+    "TIMESTAMPTZ" => format_offset_datetime(decode::<Postgres, _>(raw)?).into(),
+    "INTERVAL" => format_interval(decode::<Postgres, _>(raw)?).into(),
+    _ => return postgres_fallback(raw),
+  };
+  Ok(value)
+}
+
+/// Types such as `inet`, `money`, ranges or composites, and arrays of them: the text the
+/// server sent, or the raw bytes of its binary format.
+fn postgres_fallback(raw: PgValueRef<'_>) -> Result<JsonValue> {
+  match raw.format() {
+    PgValueFormat::Text => Ok(JsonValue::String(decode::<Postgres, _>(raw)?)),
+    PgValueFormat::Binary => Ok(bytes_json(decode::<Postgres, _>(raw)?)),
+  }
+}
+
+/// One-dimensional arrays of the scalar types above; NULL elements become `null`.
+fn decode_postgres_array(element: &str, raw: PgValueRef<'_>, numeric: NumericPolicy) -> Result<JsonValue> {
+  match element {
+    "BOOL" => array(raw, JsonValue::Bool),
+    "INT2" => array(raw, |v: i16| v.into()),
+    "INT4" => array(raw, |v: i32| v.into()),
+    "INT8" => array(raw, |v: i64| int_json(v, numeric)),
+    "FLOAT4" => array(raw, float32_json),
+    "FLOAT8" => array(raw, float_json),
+    "NUMERIC" => {
+      let items: Vec<Option<Vec<u8>>> = decode::<Postgres, _>(raw)?;
+      let items = items
+        .into_iter()
+        .map(|item| item.map_or(Ok(JsonValue::Null), |bytes| numeric_json(&bytes, numeric)))
+        .collect::<Result<_>>()?;
+      Ok(JsonValue::Array(items))
+    }
+    "TEXT" | "VARCHAR" | "CHAR" | "BPCHAR" | "NAME" | "CITEXT" => array(raw, JsonValue::String),
+    "UUID" => array(raw, |v: Uuid| v.to_string().into()),
+    "JSON" | "JSONB" => array(raw, |v: JsonValue| v),
+    "BYTEA" => array(raw, bytes_json),
+    "DATE" => array(raw, |v: Date| format_date(v).into()),
+    "TIME" => array(raw, |v: Time| format_time(v).into()),
+    "TIMESTAMP" => array(raw, |v: PrimitiveDateTime| format_datetime(v).into()),
+    "TIMESTAMPTZ" => array(raw, |v: OffsetDateTime| format_offset_datetime(v).into()),
+    "INTERVAL" => array(raw, |v: PgInterval| format_interval(v).into()),
+    _ => postgres_fallback(raw),
+  }
+}
+
+fn array<T>(raw: PgValueRef<'_>, to_json: impl Fn(T) -> JsonValue) -> Result<JsonValue>
+where
+  T: for<'a> Decode<'a, Postgres> + Type<Postgres>,
+{
+  let items: Vec<Option<T>> = decode::<Postgres, _>(raw)?;
+  Ok(JsonValue::Array(
+    items
+      .into_iter()
+      .map(|item| item.map_or(JsonValue::Null, &to_json))
+      .collect(),
+  ))
+}
+
+fn decode<'r, DB: Database, T: Decode<'r, DB>>(raw: DB::ValueRef<'r>) -> Result<T> {
+  T::decode(raw).map_err(|e| sqlx::Error::Decode(e).into())
+}
+
+/// JSON has no NaN or infinities, so those become the strings JavaScript prints for them.
+fn float_json(v: f64) -> JsonValue {
+  match serde_json::Number::from_f64(v) {
+    Some(n) => JsonValue::Number(n),
+    None if v.is_nan() => "NaN".into(),
+    None if v > 0.0 => "Infinity".into(),
+    None => "-Infinity".into(),
+  }
+}
+
+/// Widens through the shortest decimal form, so `0.1f32` stays `0.1` rather than `0.10000000149011612`.
+fn float32_json(v: f32) -> JsonValue {
+  float_json(v.to_string().parse().unwrap_or(f64::from(v)))
+}
+
+fn bytes_json(bytes: Vec<u8>) -> JsonValue {
+  JsonValue::Array(bytes.into_iter().map(JsonValue::from).collect())
+}
+
+/// The largest integer a JavaScript number holds exactly (`Number.MAX_SAFE_INTEGER`).
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+fn int_json(v: i64, numeric: NumericPolicy) -> JsonValue {
+  match numeric {
+    NumericPolicy::Lossless if v.unsigned_abs() > MAX_SAFE_INTEGER => JsonValue::String(v.to_string()),
+    _ => JsonValue::Number(v.into()),
+  }
+}
+
+fn uint_json(v: u64, numeric: NumericPolicy) -> JsonValue {
+  match numeric {
+    NumericPolicy::Lossless if v > MAX_SAFE_INTEGER => JsonValue::String(v.to_string()),
+    _ => JsonValue::Number(v.into()),
+  }
+}
+
+fn decimal_json(text: String, numeric: NumericPolicy) -> JsonValue {
+  match numeric {
+    NumericPolicy::Lossless => JsonValue::String(text),
+    NumericPolicy::Number => match text.parse::<f64>() {
+      Ok(v) => float_json(v),
+      Err(_) => JsonValue::String(text),
+    },
+  }
+}
+
+fn numeric_json(bytes: &[u8], numeric: NumericPolicy) -> Result<JsonValue> {
+  pg_numeric_to_string(bytes)
+    .map(|text| decimal_json(text, numeric))
+    .ok_or_else(|| Error::UnsupportedDatatype("malformed NUMERIC value".to_string()))
+}
+
+/// Renders Postgres' binary NUMERIC format (base-10000 digits with a weight and display scale)
+/// as an exact decimal string.
+pub(crate) fn pg_numeric_to_string(bytes: &[u8]) -> Option<String> {
+  let read = |i: usize| bytes.get(i * 2..i * 2 + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+  let ndigits = read(0)? as usize;
+  let weight = i32::from(read(1)? as i16);
+  let sign = read(2)?;
+  let dscale = read(3)? as usize;
+  match sign {
+    0xC000 => return Some("NaN".to_string()),
+    0xD000 => return Some("Infinity".to_string()),
+    0xF000 => return Some("-Infinity".to_string()),
+    _ => {}
+  }
+  let digits = (0..ndigits).map(|i| read(4 + i)).collect::<Option<Vec<u16>>>()?;
+  // Digit `i` is worth 10000^(weight - i); positions outside the stored digits are zero.
+  let digit = |pos: i32| usize::try_from(pos).ok().and_then(|pos| digits.get(pos)).copied().unwrap_or(0);
+
+  let mut out = String::new();
+  if sign == 0x4000 {
+    out.push('-');
+  }
+  if weight < 0 {
+    out.push('0');
+  } else {
+    let _ = write!(out, "{}", digit(0));
+    for pos in 1..=weight {
+      let _ = write!(out, "{:04}", digit(pos));
+    }
+  }
+  if dscale > 0 {
+    let mut fraction = String::new();
+    let mut pos = weight + 1;
+    while fraction.len() < dscale {
+      let _ = write!(fraction, "{:04}", digit(pos));
+      pos += 1;
+    }
+    fraction.truncate(dscale);
+    out.push('.');
+    out.push_str(&fraction);
+  }
+  Some(out)
+}
+
+pub(crate) fn format_date(date: Date) -> String {
+  format!("{:04}-{:02}-{:02}", date.year(), u8::from(date.month()), date.day())
+}
+
+pub(crate) fn format_time(time: Time) -> String {
+  let mut out = format!("{:02}:{:02}:{:02}", time.hour(), time.minute(), time.second());
+  push_fraction(&mut out, time.nanosecond(), 9);
+  out
+}
+
+pub(crate) fn format_datetime(datetime: PrimitiveDateTime) -> String {
+  format!("{}T{}", format_date(datetime.date()), format_time(datetime.time()))
+}
+
+pub(crate) fn format_offset_datetime(datetime: OffsetDateTime) -> String {
+  format!(
+    "{}T{}{}",
+    format_date(datetime.date()),
+    format_time(datetime.time()),
+    format_offset(datetime.offset())
+  )
+}
+
+fn format_offset(offset: UtcOffset) -> String {
+  if offset.is_utc() {
+    return "Z".to_string();
+  }
+  let (hours, minutes, _) = offset.as_hms();
+  let sign = if offset.is_negative() { '-' } else { '+' };
+  format!("{sign}{:02}:{:02}", hours.unsigned_abs(), minutes.unsigned_abs())
+}
+
+/// MySQL TIME is a signed duration that may exceed 24 hours.
+fn format_mysql_time(time: MySqlTime) -> String {
+  let sign = if time.is_negative() { "-" } else { "" };
+  let mut out = format!("{sign}{:02}:{:02}:{:02}", time.hours(), time.minutes(), time.seconds());
+  push_fraction(&mut out, time.microseconds(), 6);
+  out
+}
+
+/// ISO-8601 duration, e.g. `P1Y2M3DT4H5M6.5S`.
+pub(crate) fn format_interval(interval: PgInterval) -> String {
+  let mut out = String::from("P");
+  let (years, months) = (interval.months / 12, interval.months % 12);
+  if years != 0 {
+    let _ = write!(out, "{years}Y");
+  }
+  if months != 0 {
+    let _ = write!(out, "{months}M");
+  }
+  if interval.days != 0 {
+    let _ = write!(out, "{}D", interval.days);
+  }
+
+  let micros = interval.microseconds;
+  if micros != 0 {
+    out.push('T');
+    let hours = micros / 3_600_000_000;
+    let minutes = micros / 60_000_000 % 60;
+    let seconds = micros % 60_000_000;
+    if hours != 0 {
+      let _ = write!(out, "{hours}H");
+    }
+    if minutes != 0 {
+      let _ = write!(out, "{minutes}M");
+    }
+    if seconds != 0 {
+      let sign = if seconds < 0 { "-" } else { "" };
+      let _ = write!(out, "{sign}{}", (seconds / 1_000_000).unsigned_abs());
+      push_fraction(&mut out, (seconds % 1_000_000).unsigned_abs() as u32, 6);
+      out.push('S');
+    }
+  }
+  if out == "P" {
+    out.push_str("T0S");
+  }
+  out
+}
+
+/// Appends `.` and the fractional digits of `value` (out of `10^digits`) without trailing zeros.
+fn push_fraction(out: &mut String, value: u32, digits: usize) {
+  if value > 0 {
+    let fraction = format!("{value:0digits$}");
+    out.push('.');
+    out.push_str(fraction.trim_end_matches('0'));
+  }
+}
@@ -1,115 +1,579 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+  collections::{hash_map::Entry, HashMap, HashSet},
+  future::Future,
+  path::{Component, Path, PathBuf},
+  pin::Pin,
+  str::FromStr,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, Weak,
+  },
+  time::{Duration, Instant},
+};
 
 use indexmap::IndexMap;
-use serde::de::DeserializeOwned;
 use serde_json::Value as JsonValue;
-use sqlx::{Column, Row};
-use tauri::{plugin::PluginApi, AppHandle, Manager, Runtime};
-use tokio::sync::RwLock;
+use sqlx::{
+  mysql::{MySqlConnectOptions, MySqlTransactionManager},
+  pool::PoolConnection,
+  postgres::PgConnectOptions,
+  sqlite::SqliteConnectOptions,
+  Executor, TransactionManager,
+};
+use tauri::{plugin::PluginApi, AppHandle, Manager, Runtime, Webview};
+use tokio::sync::{Mutex as AsyncMutex, RwLock};
 use uuid::Uuid;
 
-use crate::{models::*, Error, Result};
+use crate::{
+  bind::{bind_value_mysql, bind_value_postgres, bind_value_sqlite},
+  decode::{row_to_blob, rows_to_json_mysql, rows_to_json_postgres, rows_to_json_sqlite},
+  error::redact_credentials,
+  models::*,
+  scope::{ConnectScope, ScopeTarget},
+  Error, Result,
+};
 
-pub fn init<R: Runtime, C: DeserializeOwned>(
+/// Savepoint wrapping a batch run inside an existing transaction. It is released before the
+/// call returns, and savepoint commands act on the innermost one with a given name, so it
+/// cannot interfere with savepoints the caller created.
+const BATCH_SAVEPOINT: &str = "__sql_transaction_batch";
+
+/// How often the background task looks for transactions past their idle timeout.
+const REAPER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the id of a reaped transaction is remembered, so callers get "expired" rather
+/// than "not found".
+const EXPIRED_RETENTION: Duration = Duration::from_secs(600);
+
+pub fn init<R: Runtime>(
   app: &AppHandle<R>,
-  _api: PluginApi<R, C>,
+  api: PluginApi<R, Option<Config>>,
+  idle_timeout: Option<Duration>,
+  queries: HashMap<String, String>,
 ) -> Result<SqlTransaction<R>> {
-  Ok(SqlTransaction::new(app.clone()))
+  let mut config = api.config().clone().unwrap_or_default();
+  // Queries registered on the builder take precedence over the config's.
+  config.queries.extend(queries);
+  let preload = config.preload.clone();
+  let sql_transaction = SqlTransaction::with_config(app.clone(), idle_timeout, config);
+  tauri::async_runtime::block_on(sql_transaction.preload(&preload))?;
+  Ok(sql_transaction)
 }
 
 /// Access to the sql-transaction APIs.
-#[derive(Clone)]
 pub struct SqlTransaction<R: Runtime> {
   app: AppHandle<R>,
   state: Arc<SqlState>,
 }
 
+impl<R: Runtime> Clone for SqlTransaction<R> {
+  fn clone(&self) -> Self {
+    Self {
+      app: self.app.clone(),
+      state: self.state.clone(),
+    }
+  }
+}
+
+/// The maps are only locked long enough to look an entry up; queries run against a cloned
+/// pool or under the transaction's own mutex, so one slow statement never blocks the others.
 #[derive(Default)]
 struct SqlState {
-  pools: RwLock<HashMap<String, DbPool>>, // key: db url/handle
-  txs: RwLock<HashMap<Uuid, Box<dyn DbTransaction>>>, // key: tx id
+  pools: RwLock<HashMap<String, Database>>, // key: handle returned by connect
+  txs: RwLock<HashMap<Uuid, Arc<TxSlot>>>, // key: tx id
+  expired: Mutex<HashMap<Uuid, Instant>>, // tx ids rolled back by the reaper, and when
+  idle_timeout: Option<Duration>, // default for transactions that don't set their own
+  config: Config,
+}
+
+/// A transaction shared between the map and whoever is using it. The inner value is taken
+/// out once the transaction is committed or rolled back, so callers that looked the slot up
+/// just before that see it as finished.
+struct TxSlot {
+  db: String, // handle of the pool the transaction runs on
+  target: ScopeTarget, // the database's scope target
+  owner: Option<TxOwner>, // None when begun from Rust
+  tx: AsyncMutex<Option<OpenTransaction>>,
+}
+
+/// The webview that began a transaction, and the window hosting it.
+struct TxOwner {
+  webview: String,
+  window: String,
+}
+
+/// An open transaction together with the savepoints created inside it,
+/// innermost last.
+struct OpenTransaction {
+  tx: Box<dyn DbTransaction>,
+  savepoints: Vec<String>,
+  numeric: NumericPolicy, // the database's policy, for selects that don't set their own
+  read_only: bool,
+  idle_timeout: Option<Duration>,
+  last_used: Instant,
+}
+
+impl OpenTransaction {
+  fn is_expired(&self, now: Instant) -> bool {
+    self
+      .idle_timeout
+      .is_some_and(|timeout| now.duration_since(self.last_used) >= timeout)
+  }
+}
+
+impl SqlState {
+  /// Rolls back every transaction that has been idle for longer than its timeout.
+  async fn reap_expired(&self) {
+    let now = Instant::now();
+    self
+      .expired
+      .lock()
+      .unwrap()
+      .retain(|_, reaped| now.duration_since(*reaped) < EXPIRED_RETENTION);
+    let slots: Vec<(Uuid, Arc<TxSlot>)> = self
+      .txs
+      .read()
+      .await
+      .iter()
+      .map(|(id, slot)| (*id, slot.clone()))
+      .collect();
+
+    let mut expired = Vec::new();
+    for (id, slot) in slots {
+      // A locked transaction is running a statement right now, so it is not idle.
+      let Ok(mut guard) = slot.tx.try_lock() else {
+        continue;
+      };
+      if guard.as_ref().is_some_and(|entry| entry.is_expired(now)) {
+        // Record the id before the slot disappears so callers never see a plain "not found".
+        self.expired.lock().unwrap().insert(id, now);
+        expired.extend(guard.take().map(|entry| (id, entry)));
+      }
+    }
+
+    if expired.is_empty() {
+      return;
+    }
+    let mut txs = self.txs.write().await;
+    for (id, _) in &expired {
+      txs.remove(id);
+    }
+    drop(txs);
+
+    for (_, entry) in expired {
+      // The connection is discarded by the pool if the rollback fails, so there is nothing left to do.
+      let _ = entry.tx.rollback().await;
+    }
+  }
+}
+
+fn spawn_reaper(state: Weak<SqlState>) {
+  tauri::async_runtime::spawn(async move {
+    let mut interval = tokio::time::interval(REAPER_INTERVAL);
+    loop {
+      interval.tick().await;
+      match state.upgrade() {
+        Some(state) => state.reap_expired().await,
+        None => break,
+      }
+    }
+  });
+}
+
+/// A connected database and the settings it was connected with.
+#[derive(Clone)]
+struct Database {
+  pool: DbPool,
+  numeric: NumericPolicy,
+  read_only: bool,
+  target: ScopeTarget, // what the database was connected to, for checking callers' scopes
+  owner: Option<String>, // label of the webview that connected it; None when connected from Rust
+  closed: Arc<AtomicBool>, // set once `close` has taken it out of the map
 }
 
+impl Database {
+  /// MySQL and Postgres sessions can be switched back to read-write by any statement, so on
+  /// a read-only database each select runs in a `READ ONLY` transaction of its own.
+  fn isolates_selects(&self) -> bool {
+    self.read_only && !matches!(self.pool, DbPool::Sqlite(_))
+  }
+}
+
+#[derive(Clone)]
 enum DbPool {
   Sqlite(sqlx::Pool<sqlx::Sqlite>),
   MySql(sqlx::Pool<sqlx::MySql>),
   Postgres(sqlx::Pool<sqlx::Postgres>),
 }
 
+impl DbPool {
+  /// Waits for checked-out connections to be returned, then closes them all.
+  async fn close(&self) {
+    match self {
+      DbPool::Sqlite(pool) => {
+        // Fold the WAL back into the database so no `-wal` file is left next to it. A no-op
+        // outside WAL mode; a failure just leaves the checkpoint to the next open.
+        let _ = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(pool).await;
+        pool.close().await
+      }
+      DbPool::MySql(pool) => pool.close().await,
+      DbPool::Postgres(pool) => pool.close().await,
+    }
+  }
+}
+
+/// Maps the request's pool settings onto sqlx's, leaving unset ones at their defaults.
+fn pool_options<DB: sqlx::Database>(options: &PoolOptions) -> sqlx::pool::PoolOptions<DB> {
+  let optional = |ms: u64| (ms > 0).then(|| Duration::from_millis(ms));
+  let mut pool = sqlx::pool::PoolOptions::<DB>::new();
+  if let Some(max) = options.max_connections {
+    pool = pool.max_connections(max);
+  }
+  if let Some(min) = options.min_connections {
+    pool = pool.min_connections(min);
+  }
+  if let Some(ms) = options.acquire_timeout_ms {
+    pool = pool.acquire_timeout(Duration::from_millis(ms));
+  }
+  if let Some(ms) = options.idle_timeout_ms {
+    pool = pool.idle_timeout(optional(ms));
+  }
+  if let Some(ms) = options.max_lifetime_ms {
+    pool = pool.max_lifetime(optional(ms));
+  }
+  if let Some(test) = options.test_before_acquire {
+    pool = pool.test_before_acquire(test);
+  }
+  pool
+}
+
+/// Whether `..` components take a relative path above the directory it is joined to.
+fn escapes_base(path: &Path) -> bool {
+  let mut depth = 0usize;
+  for component in path.components() {
+    match component {
+      Component::ParentDir => match depth.checked_sub(1) {
+        Some(parent) => depth = parent,
+        None => return true,
+      },
+      Component::Normal(_) => depth += 1,
+      _ => {}
+    }
+  }
+  false
+}
+
+/// Whether a SQLite URL names the in-memory database, spelled the way sqlx accepts it.
+fn is_sqlite_memory(url: &str) -> bool {
+  let database = url.trim_start_matches("sqlite://").trim_start_matches("sqlite:");
+  database.split('?').next() == Some(":memory:")
+}
+
+/// Applies the `sqlite` options to the parsed connect options of a SQLite pool.
+fn sqlite_connect_options(mut connect: SqliteConnectOptions, options: &SqliteOptions) -> Result<SqliteConnectOptions> {
+  if let Some(mode) = options.journal_mode {
+    connect = connect.journal_mode(match mode {
+      SqliteJournalMode::Delete => sqlx::sqlite::SqliteJournalMode::Delete,
+      SqliteJournalMode::Truncate => sqlx::sqlite::SqliteJournalMode::Truncate,
+      SqliteJournalMode::Persist => sqlx::sqlite::SqliteJournalMode::Persist,
+      SqliteJournalMode::Memory => sqlx::sqlite::SqliteJournalMode::Memory,
+      SqliteJournalMode::Wal => sqlx::sqlite::SqliteJournalMode::Wal,
+      SqliteJournalMode::Off => sqlx::sqlite::SqliteJournalMode::Off,
+    });
+  }
+  if let Some(synchronous) = options.synchronous {
+    connect = connect.synchronous(match synchronous {
+      SqliteSynchronous::Off => sqlx::sqlite::SqliteSynchronous::Off,
+      SqliteSynchronous::Normal => sqlx::sqlite::SqliteSynchronous::Normal,
+      SqliteSynchronous::Full => sqlx::sqlite::SqliteSynchronous::Full,
+      SqliteSynchronous::Extra => sqlx::sqlite::SqliteSynchronous::Extra,
+    });
+  }
+  if let Some(on) = options.foreign_keys {
+    connect = connect.foreign_keys(on);
+  }
+  if let Some(ms) = options.busy_timeout_ms {
+    connect = connect.busy_timeout(Duration::from_millis(ms));
+  }
+  if let Some(create) = options.create_if_missing {
+    connect = connect.create_if_missing(create);
+  }
+  if let Some(read_only) = options.read_only {
+    connect = connect.read_only(read_only);
+  }
+  // Both parts are interpolated into the PRAGMA statement, so only plain tokens are accepted.
+  let plain = |s: &str| {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+  };
+  for (name, value) in &options.pragmas {
+    if !plain(name) || !plain(value) {
+      return Err(Error::InvalidRequest(format!("invalid pragma: {name} = {value}")));
+    }
+    connect = connect.pragma(name.clone(), value.clone());
+  }
+  Ok(connect)
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
 trait DbTransaction: Send + Sync {
-  fn execute(&mut self, query: String, values: Vec<JsonValue>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(u64, Option<String>)>> + Send + '_>>;
-  fn commit(self: Box<Self>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>>;
-  fn rollback(self: Box<Self>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>>;
+  fn execute(&mut self, query: String, values: Vec<JsonValue>) -> BoxFuture<'_, Result<(u64, Option<String>)>>;
+  fn select(&mut self, query: String, values: Vec<JsonValue>, numeric: NumericPolicy) -> BoxFuture<'_, Result<Vec<IndexMap<String, JsonValue>>>>;
+  fn select_blob(&mut self, query: String, values: Vec<JsonValue>) -> BoxFuture<'_, Result<Vec<u8>>>;
+  /// Runs a statement without bind parameters through the simple query protocol.
+  fn execute_raw(&mut self, sql: String) -> BoxFuture<'_, Result<()>>;
+  fn commit(self: Box<Self>) -> BoxFuture<'static, Result<()>>;
+  fn rollback(self: Box<Self>) -> BoxFuture<'static, Result<()>>;
 }
 
 struct SqliteTransaction(sqlx::Transaction<'static, sqlx::Sqlite>);
-struct MySqlTransaction(sqlx::Transaction<'static, sqlx::MySql>);
+/// Owns the pooled connection instead of wrapping `sqlx::Transaction`, which consumes it at
+/// begin, so a connection left with a pending isolation level can still be closed.
+struct MySqlTransaction(PoolConnection<sqlx::MySql>);
 struct PostgresTransaction(sqlx::Transaction<'static, sqlx::Postgres>);
 
 impl<R: Runtime> SqlTransaction<R> {
-  pub fn new(app: AppHandle<R>) -> Self {
-    Self {
-      app,
-      state: Arc::new(SqlState::default()),
-    }
+  pub fn new(app: AppHandle<R>, idle_timeout: Option<Duration>) -> Self {
+    Self::with_config(app, idle_timeout, Config::default())
+  }
+
+  pub(crate) fn with_config(app: AppHandle<R>, idle_timeout: Option<Duration>, config: Config) -> Self {
+    let state = Arc::new(SqlState {
+      idle_timeout,
+      config,
+      ..Default::default()
+    });
+    spawn_reaper(Arc::downgrade(&state));
+    Self { app, state }
   }
 
   pub async fn connect(&self, payload: ConnectRequest) -> Result<ConnectResponse> {
-    let pool = Self::create_pool(&payload.url, &self.app).await?;
-    let mut guard = self.state.pools.write().await;
-    guard.insert(payload.url.clone(), pool);
-    Ok(ConnectResponse {
-      handle: payload.url,
-    })
+    self.connect_in_scope(payload, &ConnectScope::default(), None).await
   }
 
-  async fn create_pool<R2: Runtime>(url: &str, app: &AppHandle<R2>) -> Result<DbPool> {
+  /// Connects only if the database is allowed by `scope`, the capability scope of the caller.
+  /// The database belongs to `webview`, which is then the only webview that may close it.
+  pub(crate) async fn connect_in_scope(
+    &self,
+    payload: ConnectRequest,
+    scope: &ConnectScope,
+    webview: Option<&Webview<R>>,
+  ) -> Result<ConnectResponse> {
+    let handle = match &payload.alias {
+      Some(alias) if alias.trim().is_empty() => {
+        return Err(Error::InvalidRequest("database alias cannot be empty".to_string()))
+      }
+      Some(alias) => alias.clone(),
+      None => Uuid::new_v4().to_string(),
+    };
+    // Taking over a loaded alias would redirect every window using it to another database.
+    if self.state.pools.read().await.contains_key(&handle) {
+      return Err(Error::AliasInUse(handle));
+    }
+
+    let mut database = Self::create_database(&payload, &self.app, &self.state.config, scope).await?;
+    database.owner = webview.map(|webview| webview.label().to_string());
+    match self.state.pools.write().await.entry(handle.clone()) {
+      // Another connect with the same alias finished first.
+      Entry::Occupied(_) => {
+        tauri::async_runtime::spawn(async move { database.pool.close().await });
+        Err(Error::AliasInUse(handle))
+      }
+      Entry::Vacant(entry) => {
+        entry.insert(database);
+        Ok(ConnectResponse { handle })
+      }
+    }
+  }
+
+  /// Connects the databases declared in the plugin config under their aliases. A required
+  /// database that fails to connect aborts, the others are skipped.
+  pub(crate) async fn preload(&self, databases: &[PreloadDatabase]) -> Result<()> {
+    for database in databases {
+      let result = self
+        .connect(ConnectRequest {
+          url: database.url.clone(),
+          alias: Some(database.alias.clone()),
+          pool: database.pool.clone(),
+          sqlite: database.sqlite.clone(),
+          numeric: database.numeric,
+          read_only: database.read_only,
+        })
+        .await;
+      if let Err(err) = result {
+        if database.required {
+          return Err(Error::PreloadFailed {
+            alias: database.alias.clone(),
+            source: Box::new(err),
+          });
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Opens the pool for `payload`. A read-only database (requested, or imposed by the scope)
+  /// is also read-only at the connection level: SQLite files are opened with `mode=ro` and
+  /// `query_only`, Postgres and MySQL sessions default to read-only transactions.
+  async fn create_database<R2: Runtime>(payload: &ConnectRequest, app: &AppHandle<R2>, config: &Config, scope: &ConnectScope) -> Result<Database> {
+    let url = payload.url.as_str();
+    let options = &payload.pool;
+    if options.max_connections == Some(0) {
+      return Err(Error::InvalidRequest("maxConnections must be at least 1".to_string()));
+    }
+    if let (Some(min), Some(max)) = (options.min_connections, options.max_connections) {
+      if min > max {
+        return Err(Error::InvalidRequest(format!(
+          "minConnections ({min}) is greater than maxConnections ({max})"
+        )));
+      }
+    }
+
     let scheme = url.split_once(':')
-      .ok_or_else(|| Error::DatabaseNotLoaded(format!("Invalid URL: {}", url)))?
+      .ok_or_else(|| Error::DatabaseNotLoaded(format!("Invalid URL: {}", redact_credentials(url))))?
       .0;
 
+    if scheme != "sqlite" && payload.sqlite != SqliteOptions::default() {
+      return Err(Error::UnsupportedOption(format!(
+        "sqlite options cannot be used with a {scheme} database"
+      )));
+    }
+
+    let alias = payload.alias.as_deref();
     match scheme {
       "sqlite" => {
-        let path = Self::map_sqlite_path(url, app)?;
-        let pool = sqlx::SqlitePool::connect(&path).await?;
-        Ok(DbPool::Sqlite(pool))
+        let base_dir = payload.sqlite.base_dir.or(config.sqlite_base_dir).unwrap_or_default();
+        let connect_options = Self::map_sqlite_path(url, base_dir, config.allow_path_traversal, app)?;
+        let mut connect_options = sqlite_connect_options(connect_options, &payload.sqlite)?;
+        // The file sqlx will open, without query parameters such as `?mode=rwc` and with
+        // percent-escapes decoded.
+        let file = if is_sqlite_memory(url) {
+          PathBuf::from(":memory:")
+        } else {
+          connect_options.get_filename().to_path_buf()
+        };
+        let target = ScopeTarget {
+          scheme: "sqlite",
+          path: Some(file),
+          host: None,
+          alias: alias.map(str::to_string),
+        };
+        // `sqlite.readOnly` makes the handle read-only too, so writes fail as `readOnly`
+        // rather than as a database error.
+        let read_only =
+          scope.check(&target, app)? || payload.read_only || payload.sqlite.read_only == Some(true);
+        if read_only {
+          connect_options = connect_options
+            .read_only(true)
+            .create_if_missing(false)
+            .pragma("query_only", "ON");
+        }
+        let pool = pool_options(options).connect_with(connect_options).await?;
+        Ok((DbPool::Sqlite(pool), read_only, target))
       }
       "mysql" => {
-        let pool = sqlx::MySqlPool::connect(url).await?;
-        Ok(DbPool::MySql(pool))
+        // Checked against what sqlx parsed, since query parameters such as `?socket=` override
+        // the URL's authority.
+        let connect_options = MySqlConnectOptions::from_str(url)?;
+        let target = server_target(
+          "mysql",
+          connect_options.get_host(),
+          connect_options.get_port(),
+          connect_options.get_socket().map(PathBuf::as_path),
+          alias,
+        );
+        let read_only = scope.check(&target, app)? || payload.read_only;
+        let mut pool = pool_options(options);
+        if read_only {
+          pool = pool.after_connect(|conn: &mut sqlx::MySqlConnection, _| {
+            Box::pin(async move {
+              conn.execute("SET SESSION TRANSACTION READ ONLY").await?;
+              Ok(())
+            })
+          });
+        }
+        Ok((DbPool::MySql(pool.connect_with(connect_options).await?), read_only, target))
       }
       "postgres" | "postgresql" => {
-        let pool = sqlx::PgPool::connect(url).await?;
-        Ok(DbPool::Postgres(pool))
+        // `?host=`, `?hostaddr=` and `?port=` override the authority, and a host starting
+        // with `/` is a Unix socket directory.
+        let mut connect_options = PgConnectOptions::from_str(url)?;
+        let host = connect_options.get_host();
+        let socket = connect_options
+          .get_socket()
+          .map(PathBuf::as_path)
+          .or_else(|| host.starts_with('/').then(|| Path::new(host)));
+        let target = server_target("postgres", host, connect_options.get_port(), socket, alias);
+        let read_only = scope.check(&target, app)? || payload.read_only;
+        if read_only {
+          connect_options = connect_options.options([("default_transaction_read_only", "on")]);
+        }
+        let pool = pool_options(options).connect_with(connect_options).await?;
+        Ok((DbPool::Postgres(pool), read_only, target))
       }
       _ => Err(Error::DatabaseNotLoaded(format!("Unsupported database type: {}", scheme))),
     }
+    .map(|(pool, read_only, target)| Database {
+      pool,
+      numeric: payload.numeric,
+      read_only,
+      target,
+      owner: None,
+      closed: Default::default(),
+    })
   }
 
-  fn map_sqlite_path<R2: Runtime>(url: &str, app: &AppHandle<R2>) -> Result<String> {
-    let db_path = url.strip_prefix("sqlite:").unwrap_or(url);
-    
-    if db_path == ":memory:" {
-      return Ok("sqlite::memory:".to_string());
+  /// Parses a SQLite URL and resolves its file against `base_dir`. The checks run on the
+  /// file name as sqlx decodes it, so `sqlite://` prefixes and percent-escapes such as
+  /// `%2e%2e` cannot hide a relative path or a `..` component.
+  fn map_sqlite_path<R2: Runtime>(
+    url: &str,
+    base_dir: SqliteBaseDir,
+    allow_traversal: bool,
+    app: &AppHandle<R2>,
+  ) -> Result<SqliteConnectOptions> {
+    let connect = SqliteConnectOptions::from_str(url)?;
+    if is_sqlite_memory(url) {
+      return Ok(connect);
+    }
+
+    let path = connect.get_filename().to_path_buf();
+    let db_path = path.display();
+    if path.is_absolute() {
+      return Ok(connect);
+    }
+    if !allow_traversal && escapes_base(&path) {
+      return Err(Error::PathNotAllowed(format!("{db_path} is outside the base directory")));
     }
 
-    let app_dir = app.path().app_config_dir()
-      .map_err(|e| Error::DatabaseNotLoaded(format!("Failed to get app dir: {}", e)))?;
+    let resolver = app.path();
+    let app_dir = match base_dir {
+      SqliteBaseDir::Config => resolver.app_config_dir(),
+      SqliteBaseDir::Data => resolver.app_data_dir(),
+      SqliteBaseDir::LocalData => resolver.app_local_data_dir(),
+      SqliteBaseDir::Cache => resolver.app_cache_dir(),
+      SqliteBaseDir::AbsoluteOnly => {
+        return Err(Error::PathNotAllowed(format!("{db_path} is not an absolute path")))
+      }
+    }
+    .map_err(|e| Error::DatabaseNotLoaded(format!("Failed to get app dir: {}", e)))?;
     
     std::fs::create_dir_all(&app_dir)
       .map_err(|e| Error::DatabaseNotLoaded(format!("Failed to create app dir: {}", e)))?;
 
-    let full_path = app_dir.join(db_path);
-    Ok(format!("sqlite:{}", full_path.display()))
+    Ok(connect.filename(app_dir.join(&path)))
   }
 
   pub async fn execute(&self, payload: ExecuteRequest) -> Result<ExecuteResponse> {
-    let guard = self.state.pools.read().await;
-    let pool = guard
-      .get(&payload.db)
-      .ok_or_else(|| Error::DatabaseNotLoaded(payload.db.clone()))?;
+    self.execute_in_scope(payload, &ConnectScope::default()).await
+  }
+
+  /// Executes unless `scope`, the caller's `connect` scope, only lets it read the database.
+  pub(crate) async fn execute_in_scope(&self, payload: ExecuteRequest, scope: &ConnectScope) -> Result<ExecuteResponse> {
+    let database = self.database(&payload.db).await?;
+    self.ensure_writable(&database.target, database.read_only, &payload.db, scope)?;
     
-    let (rows_affected, last_insert_id) = Self::execute_query(pool, &payload.query, payload.values).await?;
+    let (rows_affected, last_insert_id) = Self::execute_query(&database.pool, &payload.query, payload.values).await?;
     Ok(ExecuteResponse {
       rows_affected,
       last_insert_id,
@@ -121,7 +585,7 @@ impl<R: Runtime> SqlTransaction<R> {
       DbPool::Sqlite(pool) => {
         let mut q = sqlx::query(query);
         for value in values {
-          q = Self::bind_value_sqlite(q, value);
+          q = bind_value_sqlite(q, value)?;
         }
         let result = q.execute(pool).await?;
         Ok((result.rows_affected(), Some(result.last_insert_rowid().to_string())))
@@ -129,7 +593,7 @@ impl<R: Runtime> SqlTransaction<R> {
       DbPool::MySql(pool) => {
         let mut q = sqlx::query(query);
         for value in values {
-          q = Self::bind_value_mysql(q, value);
+          q = bind_value_mysql(q, value)?;
         }
         let result = q.execute(pool).await?;
         Ok((result.rows_affected(), Some(result.last_insert_id().to_string())))
@@ -137,7 +601,7 @@ impl<R: Runtime> SqlTransaction<R> {
       DbPool::Postgres(pool) => {
         let mut q = sqlx::query(query);
         for value in values {
-          q = Self::bind_value_postgres(q, value);
+          q = bind_value_postgres(q, value)?;
         }
         let result = q.execute(pool).await?;
         Ok((result.rows_affected(), None))
@@ -146,321 +610,812 @@ impl<R: Runtime> SqlTransaction<R> {
   }
 
   pub async fn select(&self, payload: SelectRequest) -> Result<SelectResponse> {
-    let guard = self.state.pools.read().await;
-    let pool = guard
-      .get(&payload.db)
-      .ok_or_else(|| Error::DatabaseNotLoaded(payload.db.clone()))?;
+    let database = self.database(&payload.db).await?;
+    let numeric = payload.numeric.unwrap_or(database.numeric);
+    if database.isolates_selects() {
+      let mut tx = Self::begin_on_database(&database, &Self::read_only_begin(&payload.db)).await?;
+      let rows = tx.select(payload.query, payload.values, numeric).await?;
+      tx.rollback().await?;
+      return Ok(SelectResponse { rows });
+    }
     
-    let rows = Self::select_query(pool, &payload.query, payload.values).await?;
+    let rows = Self::select_query(&database.pool, &payload.query, payload.values, numeric).await?;
     Ok(SelectResponse { rows })
   }
 
-  async fn select_query(pool: &DbPool, query: &str, values: Vec<JsonValue>) -> Result<Vec<IndexMap<String, JsonValue>>> {
+  /// Options for the rolled-back `READ ONLY` transaction wrapping one select on a read-only
+  /// MySQL or Postgres database.
+  fn read_only_begin(db: &str) -> BeginTransactionRequest {
+    BeginTransactionRequest {
+      db: db.to_string(),
+      read_only: true,
+      ..Default::default()
+    }
+  }
+
+  async fn select_query(pool: &DbPool, query: &str, values: Vec<JsonValue>, numeric: NumericPolicy) -> Result<Vec<IndexMap<String, JsonValue>>> {
     match pool {
       DbPool::Sqlite(pool) => {
         let mut q = sqlx::query(query);
         for value in values {
-          q = Self::bind_value_sqlite(q, value);
+          q = bind_value_sqlite(q, value)?;
         }
         let rows = q.fetch_all(pool).await?;
-        Self::rows_to_json_sqlite(rows)
+        rows_to_json_sqlite(rows, numeric)
       }
       DbPool::MySql(pool) => {
         let mut q = sqlx::query(query);
         for value in values {
-          q = Self::bind_value_mysql(q, value);
+          q = bind_value_mysql(q, value)?;
         }
         let rows = q.fetch_all(pool).await?;
-        Self::rows_to_json_mysql(rows)
+        rows_to_json_mysql(rows, numeric)
       }
       DbPool::Postgres(pool) => {
         let mut q = sqlx::query(query);
         for value in values {
-          q = Self::bind_value_postgres(q, value);
+          q = bind_value_postgres(q, value)?;
         }
         let rows = q.fetch_all(pool).await?;
-        Self::rows_to_json_postgres(rows)
+        rows_to_json_postgres(rows, numeric)
+      }
+    }
+  }
+
+  pub async fn begin(&self, payload: BeginTransactionRequest, webview: Option<&Webview<R>>) -> Result<BeginTransactionResponse> {
+    let database = self.database(&payload.db).await?;
+
+    let tx = Self::begin_on_database(&database, &payload).await?;
+
+    // An explicit timeout of zero opts this transaction out of the global default.
+    let idle_timeout = match payload.idle_timeout_ms {
+      Some(0) => None,
+      Some(ms) => Some(Duration::from_millis(ms)),
+      None => self.state.idle_timeout,
+    };
+
+    let slot = TxSlot {
+      db: payload.db.clone(),
+      target: database.target.clone(),
+      owner: webview.map(|webview| TxOwner {
+        webview: webview.label().to_string(),
+        window: webview.window().label().to_string(),
+      }),
+      tx: AsyncMutex::new(Some(OpenTransaction {
+        tx,
+        savepoints: Vec::new(),
+        numeric: database.numeric,
+        read_only: database.read_only,
+        idle_timeout,
+        last_used: Instant::now(),
+      })),
+    };
+
+    let tx_id = Uuid::new_v4();
+    self.state.txs.write().await.insert(tx_id, Arc::new(slot));
+    // A `close` that took the database out of the map meanwhile has already rolled back its
+    // transactions, and would wait forever for this one's connection.
+    if database.closed.load(Ordering::SeqCst) {
+      let slot = self.state.txs.write().await.remove(&tx_id);
+      if let Some(slot) = slot {
+        if let Some(entry) = slot.tx.lock().await.take() {
+          let _ = entry.tx.rollback().await;
+        }
       }
+      return Err(Error::DatabaseNotLoaded(payload.db));
     }
+
+    Ok(BeginTransactionResponse {
+      tx_id: tx_id.to_string(),
+    })
   }
 
-  pub async fn begin(&self, payload: BeginTransactionRequest) -> Result<BeginTransactionResponse> {
-    let guard = self.state.pools.read().await;
-    let pool = guard
-      .get(&payload.db)
-      .ok_or_else(|| Error::DatabaseNotLoaded(payload.db.clone()))?;
+  /// Begins on the database's pool; on a read-only MySQL or Postgres database the transaction
+  /// itself is read-only too. SQLite connections are already `query_only`.
+  async fn begin_on_database(database: &Database, payload: &BeginTransactionRequest) -> Result<Box<dyn DbTransaction>> {
+    if !database.isolates_selects() {
+      return Self::begin_on_pool(&database.pool, payload).await;
+    }
+    let payload = BeginTransactionRequest {
+      db: payload.db.clone(),
+      read_only: true,
+      ..*payload
+    };
+    let mut tx = Self::begin_on_pool(&database.pool, &payload).await?;
+    if let DbPool::Postgres(_) = database.pool {
+      // Postgres lets `SET TRANSACTION READ WRITE` undo READ ONLY until the first query has
+      // taken a snapshot; MySQL refuses it once the transaction has started.
+      tx.execute_raw("SELECT 1".to_string()).await?;
+    }
+    Ok(tx)
+  }
 
+  async fn begin_on_pool(pool: &DbPool, payload: &BeginTransactionRequest) -> Result<Box<dyn DbTransaction>> {
     let tx: Box<dyn DbTransaction> = match pool {
       DbPool::Sqlite(pool) => {
-        let tx = pool.begin().await?;
+        // SQLite transactions are always serializable, which satisfies any requested level.
+        if payload.read_only || payload.deferrable {
+          return Err(Error::UnsupportedOption(
+            "readOnly and deferrable transactions are not supported by SQLite".to_string(),
+          ));
+        }
+        let tx = match payload.sqlite_lock_mode {
+          // Take the write lock up front so concurrent writers wait on busy_timeout or fail
+          // at BEGIN instead of hitting SQLITE_BUSY when a read lock is upgraded mid-transaction.
+          Some(SqliteLockMode::Immediate) => pool.begin_with("BEGIN IMMEDIATE").await?,
+          Some(SqliteLockMode::Exclusive) => pool.begin_with("BEGIN EXCLUSIVE").await?,
+          Some(SqliteLockMode::Deferred) | None => pool.begin().await?,
+        };
         Box::new(SqliteTransaction(tx))
       }
       DbPool::MySql(pool) => {
-        let tx = pool.begin().await?;
-        Box::new(MySqlTransaction(tx))
+        if payload.deferrable {
+          return Err(Error::UnsupportedOption(
+            "deferrable transactions are not supported by MySQL".to_string(),
+          ));
+        }
+        let mut conn = pool.acquire().await?;
+        let statement = if payload.read_only {
+          "START TRANSACTION READ ONLY"
+        } else {
+          "START TRANSACTION"
+        };
+        let begin = async {
+          // MySQL has no isolation clause on START TRANSACTION; SET TRANSACTION applies to the next one only.
+          if let Some(level) = payload.isolation_level {
+            let sql = format!("SET TRANSACTION ISOLATION LEVEL {}", isolation_level_sql(level));
+            (&mut *conn).execute(sql.as_str()).await?;
+          }
+          MySqlTransactionManager::begin(&mut conn, Some(statement.into())).await
+        };
+        if let Err(err) = begin.await {
+          // The level may still be pending, and would apply to whoever checks the
+          // connection out next.
+          conn.close_on_drop();
+          return Err(err.into());
+        }
+        Box::new(MySqlTransaction(conn))
       }
       DbPool::Postgres(pool) => {
-        let tx = pool.begin().await?;
+        let mut statement = String::from("BEGIN");
+        if let Some(level) = payload.isolation_level {
+          statement.push_str(" ISOLATION LEVEL ");
+          statement.push_str(isolation_level_sql(level));
+        }
+        if payload.read_only {
+          statement.push_str(" READ ONLY");
+        }
+        if payload.deferrable {
+          statement.push_str(" DEFERRABLE");
+        }
+        let tx = pool.begin_with(statement).await?;
         Box::new(PostgresTransaction(tx))
       }
     };
-    drop(guard);
-
-    let tx_id = Uuid::new_v4();
-    self.state.txs.write().await.insert(tx_id, tx);
-
-    Ok(BeginTransactionResponse {
-      tx_id: tx_id.to_string(),
-    })
+    Ok(tx)
   }
 
-  pub async fn execute_in_tx(&self, payload: TransactionExecuteRequest) -> Result<ExecuteResponse> {
-    let tx_id = Uuid::parse_str(&payload.tx_id)
-      .map_err(|_| Error::TransactionNotFound(payload.tx_id.clone()))?;
+  pub async fn execute_in_tx(&self, payload: TransactionExecuteRequest, webview: Option<&Webview<R>>) -> Result<ExecuteResponse> {
+    self.execute_in_tx_in_scope(payload, webview, &ConnectScope::default()).await
+  }
 
-    let mut txs = self.state.txs.write().await;
-    let tx = txs
-      .get_mut(&tx_id)
-      .ok_or_else(|| Error::TransactionNotFound(payload.tx_id.clone()))?;
+  pub(crate) async fn execute_in_tx_in_scope(
+    &self,
+    payload: TransactionExecuteRequest,
+    webview: Option<&Webview<R>>,
+    scope: &ConnectScope,
+  ) -> Result<ExecuteResponse> {
+    let (tx_id, slot) = self.transaction(&payload.tx_id, webview).await?;
+    let mut guard = slot.tx.lock().await;
+    let entry = guard
+      .as_mut()
+      .ok_or_else(|| self.finished_transaction(&tx_id, &payload.tx_id))?;
+    self.ensure_writable(&slot.target, entry.read_only, &slot.db, scope)?;
 
-    let (rows_affected, last_insert_id) = tx.execute(payload.query, payload.values).await?;
+    let result = entry.tx.execute(payload.query, payload.values).await;
+    entry.last_used = Instant::now();
+    let (rows_affected, last_insert_id) = result?;
     Ok(ExecuteResponse {
       rows_affected,
       last_insert_id,
     })
   }
 
-  pub async fn commit(&self, payload: CommitRequest) -> Result<AckResponse> {
-    let tx_id = Uuid::parse_str(&payload.tx_id)
-      .map_err(|_| Error::TransactionNotFound(payload.tx_id.clone()))?;
+  pub async fn select_in_tx(&self, payload: TransactionSelectRequest, webview: Option<&Webview<R>>) -> Result<SelectResponse> {
+    let (tx_id, slot) = self.transaction(&payload.tx_id, webview).await?;
+    let mut guard = slot.tx.lock().await;
+    let entry = guard
+      .as_mut()
+      .ok_or_else(|| self.finished_transaction(&tx_id, &payload.tx_id))?;
 
-    let tx = self
-      .state
-      .txs
-      .write()
-      .await
-      .remove(&tx_id)
-      .ok_or_else(|| Error::TransactionNotFound(payload.tx_id.clone()))?;
-
-    tx.commit().await?;
-    Ok(AckResponse { ok: true })
+    let numeric = payload.numeric.unwrap_or(entry.numeric);
+    let result = entry.tx.select(payload.query, payload.values, numeric).await;
+    entry.last_used = Instant::now();
+    let rows = result?;
+    Ok(SelectResponse { rows })
   }
 
-  pub async fn rollback(&self, payload: RollbackRequest) -> Result<AckResponse> {
-    let tx_id = Uuid::parse_str(&payload.tx_id)
-      .map_err(|_| Error::TransactionNotFound(payload.tx_id.clone()))?;
+  pub async fn select_blob(&self, payload: SelectBlobRequest, webview: Option<&Webview<R>>) -> Result<Vec<u8>> {
+    match (payload.db, payload.tx_id) {
+      (None, Some(raw)) => {
+        let (tx_id, slot) = self.transaction(&raw, webview).await?;
+        let mut guard = slot.tx.lock().await;
+        let entry = guard
+          .as_mut()
+          .ok_or_else(|| self.finished_transaction(&tx_id, &raw))?;
 
-    let tx = self
-      .state
-      .txs
-      .write()
-      .await
-      .remove(&tx_id)
-      .ok_or_else(|| Error::TransactionNotFound(payload.tx_id.clone()))?;
+        let result = entry.tx.select_blob(payload.query, payload.values).await;
+        entry.last_used = Instant::now();
+        result
+      }
+      (Some(db), None) => {
+        let database = self.database(&db).await?;
+        if database.isolates_selects() {
+          let mut tx = Self::begin_on_database(&database, &Self::read_only_begin(&db)).await?;
+          let blob = tx.select_blob(payload.query, payload.values).await?;
+          tx.rollback().await?;
+          return Ok(blob);
+        }
+        Self::select_blob_query(&database.pool, &payload.query, payload.values).await
+      }
+      _ => Err(Error::InvalidRequest(
+        "select_blob needs exactly one of `db` or `txId`".to_string(),
+      )),
+    }
+  }
 
-    tx.rollback().await?;
-    Ok(AckResponse { ok: true })
+  async fn select_blob_query(pool: &DbPool, query: &str, values: Vec<JsonValue>) -> Result<Vec<u8>> {
+    match pool {
+      DbPool::Sqlite(pool) => {
+        let mut q = sqlx::query(query);
+        for value in values {
+          q = bind_value_sqlite(q, value)?;
+        }
+        row_to_blob(&q.fetch_one(pool).await?)
+      }
+      DbPool::MySql(pool) => {
+        let mut q = sqlx::query(query);
+        for value in values {
+          q = bind_value_mysql(q, value)?;
+        }
+        row_to_blob(&q.fetch_one(pool).await?)
+      }
+      DbPool::Postgres(pool) => {
+        let mut q = sqlx::query(query);
+        for value in values {
+          q = bind_value_postgres(q, value)?;
+        }
+        row_to_blob(&q.fetch_one(pool).await?)
+      }
+    }
   }
 
-  pub fn ping(&self, payload: PingRequest) -> Result<PingResponse> {
-    Ok(PingResponse {
-      value: payload.value,
-    })
+  /// Runs the query registered under `payload.name`, on `db` or inside `tx_id`, with the same
+  /// checks as `execute` and `execute_in_tx`.
+  pub async fn execute_named(&self, payload: ExecuteNamedRequest, webview: Option<&Webview<R>>) -> Result<ExecuteResponse> {
+    self.execute_named_in_scope(payload, webview, &ConnectScope::default()).await
   }
 
-  fn bind_value_sqlite<'q>(query: sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>, value: JsonValue) -> sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>> {
-    if value.is_null() {
-      query.bind(None::<String>)
-    } else if let Some(s) = value.as_str() {
-      query.bind(s.to_owned())
-    } else if let Some(n) = value.as_i64() {
-      query.bind(n)
-    } else if let Some(n) = value.as_f64() {
-      query.bind(n)
-    } else if let Some(b) = value.as_bool() {
-      query.bind(b)
-    } else {
-      query.bind(value.to_string())
+  pub(crate) async fn execute_named_in_scope(
+    &self,
+    payload: ExecuteNamedRequest,
+    webview: Option<&Webview<R>>,
+    scope: &ConnectScope,
+  ) -> Result<ExecuteResponse> {
+    let query = self.named_query(&payload.name)?;
+    match (payload.db, payload.tx_id) {
+      (None, Some(tx_id)) => {
+        self
+          .execute_in_tx_in_scope(
+            TransactionExecuteRequest {
+              tx_id,
+              query,
+              values: payload.values,
+            },
+            webview,
+            scope,
+          )
+          .await
+      }
+      (Some(db), None) => {
+        self
+          .execute_in_scope(
+            ExecuteRequest {
+              db,
+              query,
+              values: payload.values,
+            },
+            scope,
+          )
+          .await
+      }
+      _ => Err(Error::InvalidRequest(
+        "execute_named needs exactly one of `db` or `txId`".to_string(),
+      )),
     }
   }
 
-  fn bind_value_mysql<'q>(query: sqlx::query::Query<'q, sqlx::MySql, sqlx::mysql::MySqlArguments>, value: JsonValue) -> sqlx::query::Query<'q, sqlx::MySql, sqlx::mysql::MySqlArguments> {
-    if value.is_null() {
-      query.bind(None::<String>)
-    } else if let Some(s) = value.as_str() {
-      query.bind(s.to_owned())
-    } else if let Some(n) = value.as_i64() {
-      query.bind(n)
-    } else if let Some(n) = value.as_f64() {
-      query.bind(n)
-    } else if let Some(b) = value.as_bool() {
-      query.bind(b)
-    } else {
-      query.bind(value.to_string())
+  pub async fn select_named(&self, payload: SelectNamedRequest, webview: Option<&Webview<R>>) -> Result<SelectResponse> {
+    let query = self.named_query(&payload.name)?;
+    match (payload.db, payload.tx_id) {
+      (None, Some(tx_id)) => {
+        self
+          .select_in_tx(
+            TransactionSelectRequest {
+              tx_id,
+              query,
+              values: payload.values,
+              numeric: payload.numeric,
+            },
+            webview,
+          )
+          .await
+      }
+      (Some(db), None) => {
+        self
+          .select(SelectRequest {
+            db,
+            query,
+            values: payload.values,
+            numeric: payload.numeric,
+          })
+          .await
+      }
+      _ => Err(Error::InvalidRequest(
+        "select_named needs exactly one of `db` or `txId`".to_string(),
+      )),
     }
   }
 
-  fn bind_value_postgres<'q>(query: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>, value: JsonValue) -> sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments> {
-    if value.is_null() {
-      query.bind(None::<String>)
-    } else if let Some(s) = value.as_str() {
-      query.bind(s.to_owned())
-    } else if let Some(n) = value.as_i64() {
-      query.bind(n)
-    } else if let Some(n) = value.as_f64() {
-      query.bind(n)
-    } else if let Some(b) = value.as_bool() {
-      query.bind(b)
-    } else {
-      query.bind(value.to_string())
-    }
+  fn named_query(&self, name: &str) -> Result<String> {
+    self
+      .state
+      .config
+      .queries
+      .get(name)
+      .cloned()
+      .ok_or_else(|| Error::QueryNotFound(name.to_string()))
+  }
+
+  /// Runs the statements in order, either inside `tx_id` or in a transaction of their own.
+  ///
+  /// The batch is all-or-nothing: inside an existing transaction it is wrapped in a savepoint,
+  /// so a failing statement undoes the earlier ones but leaves the transaction usable.
+  pub async fn execute_batch(&self, payload: ExecuteBatchRequest, webview: Option<&Webview<R>>) -> Result<ExecuteBatchResponse> {
+    self.execute_batch_in_scope(payload, webview, &ConnectScope::default()).await
   }
 
-  fn rows_to_json_sqlite(rows: Vec<sqlx::sqlite::SqliteRow>) -> Result<Vec<IndexMap<String, JsonValue>>> {
-    let mut result = Vec::new();
-    for row in rows {
-      let mut map = IndexMap::new();
-      for (i, col) in row.columns().iter().enumerate() {
-        let value = Self::decode_sqlite_value(&row, i)?;
-        map.insert(col.name().to_string(), value);
+  pub(crate) async fn execute_batch_in_scope(
+    &self,
+    payload: ExecuteBatchRequest,
+    webview: Option<&Webview<R>>,
+    scope: &ConnectScope,
+  ) -> Result<ExecuteBatchResponse> {
+    let results = match (payload.db, payload.tx_id) {
+      (None, Some(raw)) => {
+        let (tx_id, slot) = self.transaction(&raw, webview).await?;
+        let mut guard = slot.tx.lock().await;
+        let entry = guard
+          .as_mut()
+          .ok_or_else(|| self.finished_transaction(&tx_id, &raw))?;
+        self.ensure_writable(&slot.target, entry.read_only, &slot.db, scope)?;
+
+        let result = async {
+          entry.tx.execute_raw(format!("SAVEPOINT {BATCH_SAVEPOINT}")).await?;
+          match run_batch(entry.tx.as_mut(), payload.statements).await {
+            Ok(results) => {
+              entry.tx.execute_raw(format!("RELEASE SAVEPOINT {BATCH_SAVEPOINT}")).await?;
+              Ok(results)
+            }
+            Err(err) => {
+              // The failing statement is what the caller needs to see. If undoing the batch
+              // fails too, the transaction is already broken and its own commit will say so.
+              if entry
+                .tx
+                .execute_raw(format!("ROLLBACK TO SAVEPOINT {BATCH_SAVEPOINT}"))
+                .await
+                .is_ok()
+              {
+                let _ = entry.tx.execute_raw(format!("RELEASE SAVEPOINT {BATCH_SAVEPOINT}")).await;
+              }
+              Err(err)
+            }
+          }
+        }
+        .await;
+        entry.last_used = Instant::now();
+        result?
       }
-      result.push(map);
-    }
-    Ok(result)
+      (Some(db), None) => {
+        let database = self.database(&db).await?;
+        self.ensure_writable(&database.target, database.read_only, &db, scope)?;
+        let mut tx = Self::begin_on_pool(&database.pool, &BeginTransactionRequest {
+          db,
+          ..Default::default()
+        })
+        .await?;
+        match run_batch(tx.as_mut(), payload.statements).await {
+          Ok(results) => {
+            tx.commit().await?;
+            results
+          }
+          Err(err) => {
+            let _ = tx.rollback().await;
+            return Err(err);
+          }
+        }
+      }
+      _ => {
+        return Err(Error::InvalidRequest(
+          "execute_batch needs exactly one of `db` or `txId`".to_string(),
+        ))
+      }
+    };
+
+    Ok(ExecuteBatchResponse { results })
+  }
+
+  /// Runs all steps in one transaction that is committed, or rolled back on the first
+  /// failure, before returning, so nothing is left open if the webview goes away.
+  pub async fn run_transaction(&self, payload: RunTransactionRequest) -> Result<RunTransactionResponse> {
+    self.run_transaction_in_scope(payload, &ConnectScope::default()).await
   }
 
-  fn rows_to_json_mysql(rows: Vec<sqlx::mysql::MySqlRow>) -> Result<Vec<IndexMap<String, JsonValue>>> {
-    let mut result = Vec::new();
-    for row in rows {
-      let mut map = IndexMap::new();
-      for (i, col) in row.columns().iter().enumerate() {
-        let value = Self::decode_mysql_value(&row, i)?;
-        map.insert(col.name().to_string(), value);
+  pub(crate) async fn run_transaction_in_scope(&self, payload: RunTransactionRequest, scope: &ConnectScope) -> Result<RunTransactionResponse> {
+    let database = self.database(&payload.begin.db).await?;
+    if payload.steps.iter().any(|step| matches!(step, TransactionStep::Execute { .. })) {
+      self.ensure_writable(&database.target, database.read_only, &payload.begin.db, scope)?;
+    }
+    let mut tx = Self::begin_on_database(&database, &payload.begin).await?;
+    match run_steps(tx.as_mut(), payload.steps, database.numeric).await {
+      Ok(results) => {
+        tx.commit().await?;
+        Ok(RunTransactionResponse { results })
+      }
+      Err(err) => {
+        let _ = tx.rollback().await;
+        Err(err)
       }
-      result.push(map);
     }
-    Ok(result)
   }
 
-  fn rows_to_json_postgres(rows: Vec<sqlx::postgres::PgRow>) -> Result<Vec<IndexMap<String, JsonValue>>> {
-    let mut result = Vec::new();
-    for row in rows {
-      let mut map = IndexMap::new();
-      for (i, col) in row.columns().iter().enumerate() {
-        let value = Self::decode_postgres_value(&row, i)?;
-        map.insert(col.name().to_string(), value);
+  pub async fn commit(&self, payload: CommitRequest, webview: Option<&Webview<R>>) -> Result<AckResponse> {
+    let entry = self.take_transaction(&payload.tx_id, webview).await?;
+    entry.tx.commit().await?;
+    Ok(AckResponse { ok: true })
+  }
+
+  pub async fn rollback(&self, payload: RollbackRequest, webview: Option<&Webview<R>>) -> Result<AckResponse> {
+    let entry = self.take_transaction(&payload.tx_id, webview).await?;
+    entry.tx.rollback().await?;
+    Ok(AckResponse { ok: true })
+  }
+
+  pub async fn savepoint(&self, payload: SavepointRequest, webview: Option<&Webview<R>>) -> Result<AckResponse> {
+    Self::validate_savepoint_name(&payload.name)?;
+    let (tx_id, slot) = self.transaction(&payload.tx_id, webview).await?;
+    let mut guard = slot.tx.lock().await;
+    let entry = guard
+      .as_mut()
+      .ok_or_else(|| self.finished_transaction(&tx_id, &payload.tx_id))?;
+
+    let result = entry.tx.execute_raw(format!("SAVEPOINT {}", payload.name)).await;
+    entry.last_used = Instant::now();
+    result?;
+    entry.savepoints.push(payload.name);
+    Ok(AckResponse { ok: true })
+  }
+
+  pub async fn release_savepoint(&self, payload: ReleaseSavepointRequest, webview: Option<&Webview<R>>) -> Result<AckResponse> {
+    let (tx_id, slot) = self.transaction(&payload.tx_id, webview).await?;
+    let mut guard = slot.tx.lock().await;
+    let entry = guard
+      .as_mut()
+      .ok_or_else(|| self.finished_transaction(&tx_id, &payload.tx_id))?;
+    let position = Self::find_savepoint(&entry.savepoints, &payload.name)?;
+
+    let result = entry.tx.execute_raw(format!("RELEASE SAVEPOINT {}", payload.name)).await;
+    entry.last_used = Instant::now();
+    result?;
+    // Releasing a savepoint also releases every savepoint created after it.
+    entry.savepoints.truncate(position);
+    Ok(AckResponse { ok: true })
+  }
+
+  pub async fn rollback_to_savepoint(&self, payload: RollbackToSavepointRequest, webview: Option<&Webview<R>>) -> Result<AckResponse> {
+    let (tx_id, slot) = self.transaction(&payload.tx_id, webview).await?;
+    let mut guard = slot.tx.lock().await;
+    let entry = guard
+      .as_mut()
+      .ok_or_else(|| self.finished_transaction(&tx_id, &payload.tx_id))?;
+    let position = Self::find_savepoint(&entry.savepoints, &payload.name)?;
+
+    let result = entry.tx.execute_raw(format!("ROLLBACK TO SAVEPOINT {}", payload.name)).await;
+    entry.last_used = Instant::now();
+    result?;
+    // The savepoint itself stays active; only the ones nested inside it are discarded.
+    entry.savepoints.truncate(position + 1);
+    Ok(AckResponse { ok: true })
+  }
+
+  /// Closes the pool behind `db` and forgets the handle, releasing its connections and any
+  /// lock held on a SQLite file.
+  ///
+  /// Refuses while transactions are open on it unless `force` is set, in which case they are
+  /// rolled back first.
+  /// Closes a database. A webview may only close the databases it connected, and `force` only
+  /// rolls back its own transactions; open transactions of anyone else keep the database open.
+  pub async fn close(&self, payload: CloseRequest, webview: Option<&Webview<R>>) -> Result<AckResponse> {
+    let database = {
+      // Holding the map lock keeps new connects to this handle out while the check runs.
+      let mut pools = self.state.pools.write().await;
+      let database = pools
+        .get(&payload.db)
+        .ok_or_else(|| Error::DatabaseNotLoaded(payload.db.clone()))?;
+      if !Self::owns_database(database, webview) {
+        return Err(Error::DatabaseNotOwned(payload.db));
       }
-      result.push(map);
+      let open = self.blocking_transactions(|slot| slot.db == payload.db, payload.force, webview).await;
+      if open > 0 {
+        return Err(Error::DatabaseInUse(format!("{} ({open} open)", payload.db)));
+      }
+      pools.remove(&payload.db)
+    };
+
+    if let Some(database) = database {
+      self.close_database(database, |slot| slot.db == payload.db).await;
     }
-    Ok(result)
+    Ok(AckResponse { ok: true })
   }
 
-  fn decode_sqlite_value(row: &sqlx::sqlite::SqliteRow, idx: usize) -> Result<JsonValue> {
-    use sqlx::ValueRef;
-    let raw = row.try_get_raw(idx)?;
-    if raw.is_null() {
-      return Ok(JsonValue::Null);
-    }
-    
-    // Try common types
-    if let Ok(v) = row.try_get::<i64, _>(idx) {
-      return Ok(JsonValue::Number(v.into()));
-    }
-    if let Ok(v) = row.try_get::<f64, _>(idx) {
-      return Ok(serde_json::Number::from_f64(v).map(JsonValue::Number).unwrap_or(JsonValue::Null));
-    }
-    if let Ok(v) = row.try_get::<String, _>(idx) {
-      return Ok(JsonValue::String(v));
-    }
-    if let Ok(v) = row.try_get::<bool, _>(idx) {
-      return Ok(JsonValue::Bool(v));
+  /// Closes every pool, e.g. when the app exits; from a webview, every pool it connected.
+  /// Without `force` nothing is closed while any of their transactions is still open.
+  pub async fn close_all(&self, payload: CloseAllRequest, webview: Option<&Webview<R>>) -> Result<AckResponse> {
+    let databases: Vec<(String, Database)> = {
+      let mut pools = self.state.pools.write().await;
+      let handles: HashSet<String> = pools
+        .iter()
+        .filter(|(_, database)| Self::owns_database(database, webview))
+        .map(|(handle, _)| handle.clone())
+        .collect();
+      let open = self.blocking_transactions(|slot| handles.contains(&slot.db), payload.force, webview).await;
+      if open > 0 {
+        return Err(Error::DatabaseInUse(format!("{open} open transaction(s)")));
+      }
+      handles
+        .into_iter()
+        .filter_map(|handle| pools.remove_entry(&handle))
+        .collect()
+    };
+
+    for (handle, database) in databases {
+      self.close_database(database, |slot| slot.db == handle).await;
     }
-    
-    Ok(JsonValue::Null)
+    Ok(AckResponse { ok: true })
   }
 
-  fn decode_mysql_value(row: &sqlx::mysql::MySqlRow, idx: usize) -> Result<JsonValue> {
-    use sqlx::ValueRef;
-    let raw = row.try_get_raw(idx)?;
-    if raw.is_null() {
-      return Ok(JsonValue::Null);
-    }
-    
-    if let Ok(v) = row.try_get::<i64, _>(idx) {
-      return Ok(JsonValue::Number(v.into()));
-    }
-    if let Ok(v) = row.try_get::<f64, _>(idx) {
-      return Ok(serde_json::Number::from_f64(v).map(JsonValue::Number).unwrap_or(JsonValue::Null));
-    }
-    if let Ok(v) = row.try_get::<String, _>(idx) {
-      return Ok(JsonValue::String(v));
-    }
-    if let Ok(v) = row.try_get::<bool, _>(idx) {
-      return Ok(JsonValue::Bool(v));
+  /// Counts the open transactions matching `matches` that stand in the way of closing: all of
+  /// them, or with `force` those the caller cannot roll back because they are not its own.
+  async fn blocking_transactions(&self, matches: impl Fn(&TxSlot) -> bool, force: bool, webview: Option<&Webview<R>>) -> usize {
+    let owns = |slot: &TxSlot| match (&slot.owner, webview) {
+      (_, None) => true,
+      (Some(owner), Some(webview)) => owner.webview == webview.label(),
+      (None, Some(_)) => false,
+    };
+    self
+      .state
+      .txs
+      .read()
+      .await
+      .values()
+      .filter(|slot| matches(slot) && !(force && owns(slot)))
+      .count()
+  }
+
+  /// Rolls back the transactions of a database taken out of the map, then closes its pool.
+  /// `closed` is set first, so a `begin` that raced the removal rolls itself back instead of
+  /// holding a connection the pool would wait on forever.
+  async fn close_database(&self, database: Database, matches: impl Fn(&TxSlot) -> bool) {
+    database.closed.store(true, Ordering::SeqCst);
+    self.rollback_matching(matches).await;
+    database.pool.close().await;
+  }
+
+  /// Rolls back every open transaction and closes every pool before the app exits, giving up
+  /// after `timeout` so a statement that never finishes cannot hang the exit.
+  pub(crate) async fn shutdown(&self, timeout: Duration) {
+    let _ = tokio::time::timeout(timeout, self.close_all(CloseAllRequest { force: true }, None)).await;
+  }
+
+  /// Rolls back every transaction begun by the given webview, e.g. when it reloads or navigates away.
+  pub(crate) async fn rollback_webview(&self, label: &str) {
+    self
+      .rollback_matching(|slot| slot.owner.as_ref().is_some_and(|owner| owner.webview == label))
+      .await
+  }
+
+  /// Rolls back every transaction begun by a webview hosted in the given window once it is destroyed.
+  pub(crate) async fn rollback_window(&self, label: &str) {
+    self
+      .rollback_matching(|slot| slot.owner.as_ref().is_some_and(|owner| owner.window == label))
+      .await
+  }
+
+  async fn rollback_matching(&self, matches: impl Fn(&TxSlot) -> bool) {
+    let slots: Vec<Arc<TxSlot>> = {
+      let mut txs = self.state.txs.write().await;
+      let ids: Vec<Uuid> = txs
+        .iter()
+        .filter(|(_, slot)| matches(slot))
+        .map(|(id, _)| *id)
+        .collect();
+      ids.iter().filter_map(|id| txs.remove(id)).collect()
+    };
+
+    for slot in slots {
+      // Waits for a statement that is still running on the transaction to finish first.
+      let entry = slot.tx.lock().await.take();
+      if let Some(entry) = entry {
+        let _ = entry.tx.rollback().await;
+      }
     }
-    
-    Ok(JsonValue::Null)
   }
 
-  fn decode_postgres_value(row: &sqlx::postgres::PgRow, idx: usize) -> Result<JsonValue> {
-    use sqlx::ValueRef;
-    let raw = row.try_get_raw(idx)?;
-    if raw.is_null() {
-      return Ok(JsonValue::Null);
+  async fn database(&self, db: &str) -> Result<Database> {
+    self
+      .state
+      .pools
+      .read()
+      .await
+      .get(db)
+      .cloned()
+      .ok_or_else(|| Error::DatabaseNotLoaded(db.to_string()))
+  }
+
+  /// Looks up an open transaction that the calling webview is allowed to use.
+  async fn transaction(&self, raw: &str, webview: Option<&Webview<R>>) -> Result<(Uuid, Arc<TxSlot>)> {
+    let tx_id = Uuid::parse_str(raw).map_err(|_| Error::TransactionNotFound(raw.to_string()))?;
+    let slot = self
+      .state
+      .txs
+      .read()
+      .await
+      .get(&tx_id)
+      .cloned()
+      .ok_or_else(|| self.missing_transaction(&tx_id, raw))?;
+    Self::check_owner(&slot, webview, raw)?;
+    Ok((tx_id, slot))
+  }
+
+  /// Removes a transaction so it can be committed or rolled back, once any statement still
+  /// running on it has finished.
+  async fn take_transaction(&self, raw: &str, webview: Option<&Webview<R>>) -> Result<OpenTransaction> {
+    let (tx_id, slot) = self.transaction(raw, webview).await?;
+    self.state.txs.write().await.remove(&tx_id);
+    let entry = slot.tx.lock().await.take();
+    entry.ok_or_else(|| self.finished_transaction(&tx_id, raw))
+  }
+
+  /// Refuses a write to a read-only database, or to one that `scope`, the caller's `connect`
+  /// scope, only lets it read.
+  fn ensure_writable(&self, target: &ScopeTarget, read_only: bool, handle: &str, scope: &ConnectScope) -> Result<()> {
+    if read_only || scope.read_only(target, &self.app)? {
+      return Err(Error::ReadOnly(handle.to_string()));
     }
-    
-    if let Ok(v) = row.try_get::<i64, _>(idx) {
-      return Ok(JsonValue::Number(v.into()));
+    Ok(())
+  }
+
+  /// Webviews may only close the databases they connected; calls from Rust may close any.
+  fn owns_database(database: &Database, webview: Option<&Webview<R>>) -> bool {
+    match webview {
+      Some(webview) => database.owner.as_deref() == Some(webview.label()),
+      None => true,
     }
-    if let Ok(v) = row.try_get::<i32, _>(idx) {
-      return Ok(JsonValue::Number(v.into()));
+  }
+
+  /// Webviews may only use their own transactions; calls from Rust (no webview) are always allowed.
+  fn check_owner(slot: &TxSlot, webview: Option<&Webview<R>>, raw: &str) -> Result<()> {
+    match (&slot.owner, webview) {
+      (Some(owner), Some(webview)) if owner.webview != webview.label() => {
+        Err(Error::TransactionNotOwned(raw.to_string()))
+      }
+      _ => Ok(()),
     }
-    if let Ok(v) = row.try_get::<f64, _>(idx) {
-      return Ok(serde_json::Number::from_f64(v).map(JsonValue::Number).unwrap_or(JsonValue::Null));
+  }
+
+  /// Error for a tx id that is no longer open, telling apart the ones the reaper rolled back.
+  fn missing_transaction(&self, tx_id: &Uuid, raw: &str) -> Error {
+    if self.state.expired.lock().unwrap().contains_key(tx_id) {
+      Error::TransactionExpired(raw.to_string())
+    } else {
+      Error::TransactionNotFound(raw.to_string())
     }
-    if let Ok(v) = row.try_get::<String, _>(idx) {
-      return Ok(JsonValue::String(v));
+  }
+
+  /// Error for a transaction that was finished by another call while this one waited for it.
+  fn finished_transaction(&self, tx_id: &Uuid, raw: &str) -> Error {
+    if self.state.expired.lock().unwrap().contains_key(tx_id) {
+      Error::TransactionExpired(raw.to_string())
+    } else {
+      Error::TransactionFinished(raw.to_string())
     }
-    if let Ok(v) = row.try_get::<bool, _>(idx) {
-      return Ok(JsonValue::Bool(v));
+  }
+
+  /// Savepoint names are interpolated into SQL, so only plain identifiers are accepted.
+  fn validate_savepoint_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+      && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+      && name.len() <= 63;
+    if valid {
+      Ok(())
+    } else {
+      Err(Error::InvalidSavepointName(name.to_string()))
     }
-    
-    Ok(JsonValue::Null)
+  }
+
+  fn find_savepoint(savepoints: &[String], name: &str) -> Result<usize> {
+    savepoints
+      .iter()
+      .rposition(|s| s == name)
+      .ok_or_else(|| Error::SavepointNotFound(name.to_string()))
+  }
+
+  pub fn ping(&self, payload: PingRequest) -> Result<PingResponse> {
+    Ok(PingResponse {
+      value: payload.value,
+    })
   }
 }
 
 impl DbTransaction for SqliteTransaction {
-  fn execute(&mut self, query: String, values: Vec<JsonValue>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(u64, Option<String>)>> + Send + '_>> {
+  fn execute(&mut self, query: String, values: Vec<JsonValue>) -> BoxFuture<'_, Result<(u64, Option<String>)>> {
     Box::pin(async move {
       let mut q = sqlx::query(&query);
       for value in values {
-        if value.is_null() {
-          q = q.bind(None::<String>);
-        } else if let Some(s) = value.as_str() {
-          q = q.bind(s.to_owned());
-        } else if let Some(n) = value.as_i64() {
-          q = q.bind(n);
-        } else if let Some(n) = value.as_f64() {
-          q = q.bind(n);
-        } else if let Some(b) = value.as_bool() {
-          q = q.bind(b);
-        } else {
-          q = q.bind(value.to_string());
-        }
+        q = bind_value_sqlite(q, value)?;
       }
       let result = q.execute(&mut *self.0).await?;
       Ok((result.rows_affected(), Some(result.last_insert_rowid().to_string())))
     })
   }
 
-  fn commit(self: Box<Self>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+  fn select(&mut self, query: String, values: Vec<JsonValue>, numeric: NumericPolicy) -> BoxFuture<'_, Result<Vec<IndexMap<String, JsonValue>>>> {
+    Box::pin(async move {
+      let mut q = sqlx::query(&query);
+      for value in values {
+        q = bind_value_sqlite(q, value)?;
+      }
+      let rows = q.fetch_all(&mut *self.0).await?;
+      rows_to_json_sqlite(rows, numeric)
+    })
+  }
+
+  fn select_blob(&mut self, query: String, values: Vec<JsonValue>) -> BoxFuture<'_, Result<Vec<u8>>> {
+    Box::pin(async move {
+      let mut q = sqlx::query(&query);
+      for value in values {
+        q = bind_value_sqlite(q, value)?;
+      }
+      row_to_blob(&q.fetch_one(&mut *self.0).await?)
+    })
+  }
+
+  fn execute_raw(&mut self, sql: String) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+      (&mut *self.0).execute(sql.as_str()).await?;
+      Ok(())
+    })
+  }
+
+  fn commit(self: Box<Self>) -> BoxFuture<'static, Result<()>> {
     Box::pin(async move {
       self.0.commit().await?;
       Ok(())
     })
   }
 
-  fn rollback(self: Box<Self>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+  fn rollback(self: Box<Self>) -> BoxFuture<'static, Result<()>> {
     Box::pin(async move {
       self.0.rollback().await?;
       Ok(())
@@ -469,79 +1424,242 @@ impl DbTransaction for SqliteTransaction {
 }
 
 impl DbTransaction for MySqlTransaction {
-  fn execute(&mut self, query: String, values: Vec<JsonValue>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(u64, Option<String>)>> + Send + '_>> {
+  fn execute(&mut self, query: String, values: Vec<JsonValue>) -> BoxFuture<'_, Result<(u64, Option<String>)>> {
     Box::pin(async move {
       let mut q = sqlx::query(&query);
       for value in values {
-        if value.is_null() {
-          q = q.bind(None::<String>);
-        } else if let Some(s) = value.as_str() {
-          q = q.bind(s.to_owned());
-        } else if let Some(n) = value.as_i64() {
-          q = q.bind(n);
-        } else if let Some(n) = value.as_f64() {
-          q = q.bind(n);
-        } else if let Some(b) = value.as_bool() {
-          q = q.bind(b);
-        } else {
-          q = q.bind(value.to_string());
-        }
+        q = bind_value_mysql(q, value)?;
       }
       let result = q.execute(&mut *self.0).await?;
       Ok((result.rows_affected(), Some(result.last_insert_id().to_string())))
     })
   }
 
-  fn commit(self: Box<Self>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+  fn select(&mut self, query: String, values: Vec<JsonValue>, numeric: NumericPolicy) -> BoxFuture<'_, Result<Vec<IndexMap<String, JsonValue>>>> {
     Box::pin(async move {
-      self.0.commit().await?;
+      let mut q = sqlx::query(&query);
+      for value in values {
+        q = bind_value_mysql(q, value)?;
+      }
+      let rows = q.fetch_all(&mut *self.0).await?;
+      rows_to_json_mysql(rows, numeric)
+    })
+  }
+
+  fn select_blob(&mut self, query: String, values: Vec<JsonValue>) -> BoxFuture<'_, Result<Vec<u8>>> {
+    Box::pin(async move {
+      let mut q = sqlx::query(&query);
+      for value in values {
+        q = bind_value_mysql(q, value)?;
+      }
+      row_to_blob(&q.fetch_one(&mut *self.0).await?)
+    })
+  }
+
+  fn execute_raw(&mut self, sql: String) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+      (&mut *self.0).execute(sql.as_str()).await?;
       Ok(())
     })
   }
 
-  fn rollback(self: Box<Self>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+  fn commit(mut self: Box<Self>) -> BoxFuture<'static, Result<()>> {
     Box::pin(async move {
-      self.0.rollback().await?;
+      MySqlTransactionManager::commit(&mut self.0).await?;
+      Ok(())
+    })
+  }
+
+  fn rollback(mut self: Box<Self>) -> BoxFuture<'static, Result<()>> {
+    Box::pin(async move {
+      MySqlTransactionManager::rollback(&mut self.0).await?;
       Ok(())
     })
   }
 }
 
+impl Drop for MySqlTransaction {
+  fn drop(&mut self) {
+    // Queues a ROLLBACK ahead of the connection's next use, as `sqlx::Transaction` does;
+    // a no-op once the transaction has been committed or rolled back.
+    MySqlTransactionManager::start_rollback(&mut self.0);
+  }
+}
+
 impl DbTransaction for PostgresTransaction {
-  fn execute(&mut self, query: String, values: Vec<JsonValue>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(u64, Option<String>)>> + Send + '_>> {
+  fn execute(&mut self, query: String, values: Vec<JsonValue>) -> BoxFuture<'_, Result<(u64, Option<String>)>> {
     Box::pin(async move {
       let mut q = sqlx::query(&query);
       for value in values {
-        if value.is_null() {
-          q = q.bind(None::<String>);
-        } else if let Some(s) = value.as_str() {
-          q = q.bind(s.to_owned());
-        } else if let Some(n) = value.as_i64() {
-          q = q.bind(n);
-        } else if let Some(n) = value.as_f64() {
-          q = q.bind(n);
-        } else if let Some(b) = value.as_bool() {
-          q = q.bind(b);
-        } else {
-          q = q.bind(value.to_string());
-        }
+        q = bind_value_postgres(q, value)?;
       }
       let result = q.execute(&mut *self.0).await?;
       Ok((result.rows_affected(), None))
     })
   }
 
-  fn commit(self: Box<Self>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+  fn select(&mut self, query: String, values: Vec<JsonValue>, numeric: NumericPolicy) -> BoxFuture<'_, Result<Vec<IndexMap<String, JsonValue>>>> {
+    Box::pin(async move {
+      let mut q = sqlx::query(&query);
+      for value in values {
+        q = bind_value_postgres(q, value)?;
+      }
+      let rows = q.fetch_all(&mut *self.0).await?;
+      rows_to_json_postgres(rows, numeric)
+    })
+  }
+
+  fn select_blob(&mut self, query: String, values: Vec<JsonValue>) -> BoxFuture<'_, Result<Vec<u8>>> {
+    Box::pin(async move {
+      let mut q = sqlx::query(&query);
+      for value in values {
+        q = bind_value_postgres(q, value)?;
+      }
+      row_to_blob(&q.fetch_one(&mut *self.0).await?)
+    })
+  }
+
+  fn execute_raw(&mut self, sql: String) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+      (&mut *self.0).execute(sql.as_str()).await?;
+      Ok(())
+    })
+  }
+
+  fn commit(self: Box<Self>) -> BoxFuture<'static, Result<()>> {
     Box::pin(async move {
       self.0.commit().await?;
       Ok(())
     })
   }
 
-  fn rollback(self: Box<Self>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+  fn rollback(self: Box<Self>) -> BoxFuture<'static, Result<()>> {
     Box::pin(async move {
       self.0.rollback().await?;
       Ok(())
     })
   }
 }
+
+/// The scope target of a MySQL or Postgres server: its host and port, or the Unix socket it
+/// is reached through, which only `paths` entries can admit.
+fn server_target(
+  scheme: &'static str,
+  host: &str,
+  port: u16,
+  socket: Option<&Path>,
+  alias: Option<&str>,
+) -> ScopeTarget {
+  let alias = alias.map(str::to_string);
+  match socket {
+    Some(socket) => ScopeTarget {
+      scheme,
+      path: Some(socket.to_path_buf()),
+      host: None,
+      alias,
+    },
+    None => ScopeTarget {
+      scheme,
+      path: None,
+      host: Some((host.to_string(), port)),
+      alias,
+    },
+  }
+}
+
+fn isolation_level_sql(level: IsolationLevel) -> &'static str {
+  match level {
+    IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+    IsolationLevel::ReadCommitted => "READ COMMITTED",
+    IsolationLevel::RepeatableRead => "REPEATABLE READ",
+    IsolationLevel::Serializable => "SERIALIZABLE",
+  }
+}
+
+async fn run_batch(tx: &mut dyn DbTransaction, statements: Vec<BatchStatement>) -> Result<Vec<ExecuteResponse>> {
+  let mut results = Vec::with_capacity(statements.len());
+  for (index, statement) in statements.into_iter().enumerate() {
+    let (rows_affected, last_insert_id) = tx
+      .execute(statement.query, statement.values)
+      .await
+      .map_err(|source| Error::BatchFailed {
+        index,
+        source: Box::new(source),
+      })?;
+    results.push(ExecuteResponse {
+      rows_affected,
+      last_insert_id,
+    });
+  }
+  Ok(results)
+}
+
+async fn run_steps(tx: &mut dyn DbTransaction, steps: Vec<TransactionStep>, numeric: NumericPolicy) -> Result<Vec<TransactionStepResult>> {
+  let mut results = Vec::with_capacity(steps.len());
+  // The same results as JSON, for resolving `$ref` bind values.
+  let mut resolved = Vec::with_capacity(steps.len());
+  for (index, step) in steps.into_iter().enumerate() {
+    let result = run_step(tx, step, &resolved, numeric)
+      .await
+      .map_err(|source| Error::StepFailed {
+        index,
+        source: Box::new(source),
+      })?;
+    resolved.push(serde_json::to_value(&result)?);
+    results.push(result);
+  }
+  Ok(results)
+}
+
+async fn run_step(tx: &mut dyn DbTransaction, step: TransactionStep, resolved: &[JsonValue], numeric: NumericPolicy) -> Result<TransactionStepResult> {
+  match step {
+    TransactionStep::Execute { query, values } => {
+      let values = resolve_references(values, resolved)?;
+      let (rows_affected, last_insert_id) = tx.execute(query, values).await?;
+      Ok(TransactionStepResult::Execute(ExecuteResponse {
+        rows_affected,
+        last_insert_id,
+      }))
+    }
+    TransactionStep::Select { query, values } => {
+      let values = resolve_references(values, resolved)?;
+      let rows = tx.select(query, values, numeric).await?;
+      Ok(TransactionStepResult::Select(SelectResponse { rows }))
+    }
+  }
+}
+
+/// Replaces `{ "$ref": "/<step>/<path>" }` bind values with the referenced part of an earlier result.
+fn resolve_references(values: Vec<JsonValue>, resolved: &[JsonValue]) -> Result<Vec<JsonValue>> {
+  values
+    .into_iter()
+    .map(|value| match reference_pointer(&value) {
+      Some(pointer) => resolve_reference(pointer, resolved),
+      None => Ok(value),
+    })
+    .collect()
+}
+
+fn reference_pointer(value: &JsonValue) -> Option<&str> {
+  let object = value.as_object()?;
+  if object.len() != 1 {
+    return None;
+  }
+  object.get("$ref")?.as_str()
+}
+
+fn resolve_reference(pointer: &str, resolved: &[JsonValue]) -> Result<JsonValue> {
+  let rest = pointer
+    .strip_prefix('/')
+    .ok_or_else(|| Error::InvalidRequest(format!("invalid reference: {pointer}")))?;
+  let (step, path) = rest.split_once('/').unwrap_or((rest, ""));
+  let result = step.parse::<usize>().ok().and_then(|step| resolved.get(step));
+  let value = match result {
+    Some(result) if path.is_empty() => Some(result),
+    Some(result) => result.pointer(&format!("/{path}")),
+    None => None,
+  };
+  value
+    .cloned()
+    .ok_or_else(|| Error::InvalidRequest(format!("reference does not match an earlier result: {pointer}")))
+}
@@ -1,10 +1,18 @@
+use std::{collections::HashMap, time::Duration};
+
 use tauri::{
-  plugin::{Builder, TauriPlugin},
+  plugin::{Builder as PluginBuilder, TauriPlugin},
   Manager, Runtime,
 };
+#[cfg(desktop)]
+use tauri::{webview::PageLoadEvent, RunEvent, WindowEvent};
 
 pub use models::*;
 
+#[cfg(desktop)]
+mod bind;
+#[cfg(desktop)]
+mod decode;
 #[cfg(desktop)]
 mod desktop;
 #[cfg(mobile)]
@@ -13,16 +21,22 @@ mod mobile;
 mod commands;
 mod error;
 mod models;
+mod scope;
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
-pub use error::{Error, Result};
+pub use error::{Error, ErrorDetails, ErrorKind, Result};
+pub use scope::DatabaseScope;
 
 #[cfg(desktop)]
 use desktop::SqlTransaction;
 #[cfg(mobile)]
 use mobile::SqlTransaction;
 
+#[cfg(desktop)]
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Extensions to [`tauri::App`], [`tauri::AppHandle`] and [`tauri::Window`] to access the sql-transaction APIs.
 pub trait SqlTransactionExt<R: Runtime> {
   fn sql_transaction(&self) -> &SqlTransaction<R>;
@@ -34,26 +48,111 @@ impl<R: Runtime, T: Manager<R>> crate::SqlTransactionExt<R> for T {
   }
 }
 
+/// Builder for the sql-transaction plugin.
+#[derive(Default)]
+pub struct Builder {
+  idle_timeout: Option<Duration>,
+  shutdown_timeout: Option<Duration>,
+  queries: HashMap<String, String>,
+}
+
+impl Builder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Rolls back transactions that see no activity for `timeout`, so a crashed or
+  /// reloaded frontend does not hold a pooled connection (and SQLite's write lock) forever.
+  ///
+  /// Individual transactions can override this with `idleTimeoutMs` on `begin_transaction`.
+  pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+    self.idle_timeout = Some(timeout);
+    self
+  }
+
+  /// How long to wait on exit for open transactions to roll back and pools to close.
+  /// Defaults to 5 seconds.
+  pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+    self.shutdown_timeout = Some(timeout);
+    self
+  }
+
+  /// Registers `sql` under `name`, so the frontend can run it with `executeNamed` /
+  /// `selectNamed` without being allowed to send SQL of its own. Replaces a query of the
+  /// same name from the plugin config.
+  pub fn query(mut self, name: impl Into<String>, sql: impl Into<String>) -> Self {
+    self.queries.insert(name.into(), sql.into());
+    self
+  }
+
+  pub fn build<R: Runtime>(self) -> TauriPlugin<R, Option<Config>> {
+    #[cfg(desktop)]
+    let shutdown_timeout = self.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+    let builder = PluginBuilder::<R, Option<Config>>::new("sql-transaction")
+      .invoke_handler(tauri::generate_handler![
+        commands::ping,
+        commands::connect,
+        commands::close,
+        commands::close_all,
+        commands::execute,
+        commands::select,
+        commands::execute_named,
+        commands::select_named,
+        commands::execute_batch,
+        commands::select_blob,
+        commands::run_transaction,
+        commands::begin_transaction,
+        commands::execute_in_transaction,
+        commands::select_in_transaction,
+        commands::commit,
+        commands::rollback,
+        commands::savepoint,
+        commands::release_savepoint,
+        commands::rollback_to_savepoint
+      ])
+      .setup(move |app, api| {
+        #[cfg(mobile)]
+        let sql_transaction = mobile::init(app, api)?;
+        #[cfg(desktop)]
+        let sql_transaction = desktop::init(app, api, self.idle_timeout, self.queries)?;
+        app.manage(sql_transaction);
+        Ok(())
+      });
+
+    // Transactions belong to the page that began them: a reload, navigation or closed
+    // window would otherwise leave them holding a connection with nobody to finish them.
+    #[cfg(desktop)]
+    let builder = builder
+      .on_page_load(|webview, payload| {
+        if payload.event() == PageLoadEvent::Started {
+          let app = webview.app_handle().clone();
+          let label = webview.label().to_string();
+          tauri::async_runtime::spawn(async move { app.sql_transaction().rollback_webview(&label).await });
+        }
+      })
+      .on_event(move |app, event| match event {
+        RunEvent::WindowEvent {
+          label,
+          event: WindowEvent::Destroyed,
+          ..
+        } => {
+          let app = app.clone();
+          let label = label.clone();
+          tauri::async_runtime::spawn(async move { app.sql_transaction().rollback_window(&label).await });
+        }
+        // ExitRequested can still be prevented by the app, so the databases are only shut down
+        // once the exit is final.
+        RunEvent::Exit => {
+          tauri::async_runtime::block_on(app.sql_transaction().shutdown(shutdown_timeout));
+        }
+        _ => {}
+      });
+
+    builder.build()
+  }
+}
+
 /// Initializes the plugin.
-pub fn init<R: Runtime>() -> TauriPlugin<R> {
-  Builder::new("sql-transaction")
-    .invoke_handler(tauri::generate_handler![
-      commands::ping,
-      commands::connect,
-      commands::execute,
-      commands::select,
-      commands::begin_transaction,
-      commands::execute_in_transaction,
-      commands::commit,
-      commands::rollback
-    ])
-    .setup(|app, api| {
-      #[cfg(mobile)]
-      let sql_transaction = mobile::init(app, api)?;
-      #[cfg(desktop)]
-      let sql_transaction = desktop::init(app, api)?;
-      app.manage(sql_transaction);
-      Ok(())
-    })
-    .build()
+pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
+  Builder::default().build()
 }
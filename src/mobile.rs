@@ -1,10 +1,10 @@
 use serde::de::DeserializeOwned;
 use tauri::{
   plugin::{PluginApi, PluginHandle},
-  AppHandle, Runtime,
+  AppHandle, Runtime, Webview,
 };
 
-use crate::models::*;
+use crate::{models::*, scope::ConnectScope, Error};
 
 #[cfg(target_os = "ios")]
 tauri::ios_plugin_binding!(init_plugin_sql_transaction);
@@ -38,6 +38,24 @@ impl<R: Runtime> SqlTransaction<R> {
       .run_mobile_plugin("connect", payload)
       .map_err(Into::into)
   }
+  pub(crate) fn connect_in_scope(&self, payload: ConnectRequest, scope: &ConnectScope, _webview: Option<&Webview<R>>) -> crate::Result<ConnectResponse> {
+    ensure_unscoped(scope)?;
+    self.connect(payload)
+  }
+
+  pub fn close(&self, payload: CloseRequest, _webview: Option<&Webview<R>>) -> crate::Result<AckResponse> {
+    self
+      .0
+      .run_mobile_plugin("close", payload)
+      .map_err(Into::into)
+  }
+
+  pub fn close_all(&self, payload: CloseAllRequest, _webview: Option<&Webview<R>>) -> crate::Result<AckResponse> {
+    self
+      .0
+      .run_mobile_plugin("close_all", payload)
+      .map_err(Into::into)
+  }
 
   pub fn execute(&self, payload: ExecuteRequest) -> crate::Result<ExecuteResponse> {
     self
@@ -46,6 +64,11 @@ impl<R: Runtime> SqlTransaction<R> {
       .map_err(Into::into)
   }
 
+  pub(crate) fn execute_in_scope(&self, payload: ExecuteRequest, scope: &ConnectScope) -> crate::Result<ExecuteResponse> {
+    ensure_unscoped(scope)?;
+    self.execute(payload)
+  }
+
   pub fn select(&self, payload: SelectRequest) -> crate::Result<SelectResponse> {
     self
       .0
@@ -53,31 +76,126 @@ impl<R: Runtime> SqlTransaction<R> {
       .map_err(Into::into)
   }
 
-  pub fn begin(&self, payload: BeginTransactionRequest) -> crate::Result<BeginTransactionResponse> {
+  pub fn execute_named(&self, payload: ExecuteNamedRequest, _webview: Option<&Webview<R>>) -> crate::Result<ExecuteResponse> {
+    self
+      .0
+      .run_mobile_plugin("execute_named", payload)
+      .map_err(Into::into)
+  }
+
+  pub(crate) fn execute_named_in_scope(&self, payload: ExecuteNamedRequest, webview: Option<&Webview<R>>, scope: &ConnectScope) -> crate::Result<ExecuteResponse> {
+    ensure_unscoped(scope)?;
+    self.execute_named(payload, webview)
+  }
+
+  pub fn select_named(&self, payload: SelectNamedRequest, _webview: Option<&Webview<R>>) -> crate::Result<SelectResponse> {
+    self
+      .0
+      .run_mobile_plugin("select_named", payload)
+      .map_err(Into::into)
+  }
+
+  pub fn execute_batch(&self, payload: ExecuteBatchRequest, _webview: Option<&Webview<R>>) -> crate::Result<ExecuteBatchResponse> {
+    self
+      .0
+      .run_mobile_plugin("execute_batch", payload)
+      .map_err(Into::into)
+  }
+
+  pub(crate) fn execute_batch_in_scope(&self, payload: ExecuteBatchRequest, webview: Option<&Webview<R>>, scope: &ConnectScope) -> crate::Result<ExecuteBatchResponse> {
+    ensure_unscoped(scope)?;
+    self.execute_batch(payload, webview)
+  }
+
+  pub fn select_blob(&self, payload: SelectBlobRequest, _webview: Option<&Webview<R>>) -> crate::Result<Vec<u8>> {
+    self
+      .0
+      .run_mobile_plugin("select_blob", payload)
+      .map_err(Into::into)
+  }
+
+  pub fn run_transaction(&self, payload: RunTransactionRequest) -> crate::Result<RunTransactionResponse> {
+    self
+      .0
+      .run_mobile_plugin("run_transaction", payload)
+      .map_err(Into::into)
+  }
+
+  pub(crate) fn run_transaction_in_scope(&self, payload: RunTransactionRequest, scope: &ConnectScope) -> crate::Result<RunTransactionResponse> {
+    ensure_unscoped(scope)?;
+    self.run_transaction(payload)
+  }
+
+  pub fn begin(&self, payload: BeginTransactionRequest, _webview: Option<&Webview<R>>) -> crate::Result<BeginTransactionResponse> {
     self
       .0
       .run_mobile_plugin("begin_transaction", payload)
       .map_err(Into::into)
   }
 
-  pub fn execute_in_tx(&self, payload: TransactionExecuteRequest) -> crate::Result<ExecuteResponse> {
+  pub fn execute_in_tx(&self, payload: TransactionExecuteRequest, _webview: Option<&Webview<R>>) -> crate::Result<ExecuteResponse> {
     self
       .0
       .run_mobile_plugin("execute_in_transaction", payload)
       .map_err(Into::into)
   }
 
-  pub fn commit(&self, payload: CommitRequest) -> crate::Result<AckResponse> {
+  pub(crate) fn execute_in_tx_in_scope(&self, payload: TransactionExecuteRequest, webview: Option<&Webview<R>>, scope: &ConnectScope) -> crate::Result<ExecuteResponse> {
+    ensure_unscoped(scope)?;
+    self.execute_in_tx(payload, webview)
+  }
+
+  pub fn select_in_tx(&self, payload: TransactionSelectRequest, _webview: Option<&Webview<R>>) -> crate::Result<SelectResponse> {
+    self
+      .0
+      .run_mobile_plugin("select_in_transaction", payload)
+      .map_err(Into::into)
+  }
+
+  pub fn commit(&self, payload: CommitRequest, _webview: Option<&Webview<R>>) -> crate::Result<AckResponse> {
     self
       .0
       .run_mobile_plugin("commit", payload)
       .map_err(Into::into)
   }
 
-  pub fn rollback(&self, payload: RollbackRequest) -> crate::Result<AckResponse> {
+  pub fn rollback(&self, payload: RollbackRequest, _webview: Option<&Webview<R>>) -> crate::Result<AckResponse> {
     self
       .0
       .run_mobile_plugin("rollback", payload)
       .map_err(Into::into)
   }
+
+  pub fn savepoint(&self, payload: SavepointRequest, _webview: Option<&Webview<R>>) -> crate::Result<AckResponse> {
+    self
+      .0
+      .run_mobile_plugin("savepoint", payload)
+      .map_err(Into::into)
+  }
+
+  pub fn release_savepoint(&self, payload: ReleaseSavepointRequest, _webview: Option<&Webview<R>>) -> crate::Result<AckResponse> {
+    self
+      .0
+      .run_mobile_plugin("release_savepoint", payload)
+      .map_err(Into::into)
+  }
+
+  pub fn rollback_to_savepoint(&self, payload: RollbackToSavepointRequest, _webview: Option<&Webview<R>>) -> crate::Result<AckResponse> {
+    self
+      .0
+      .run_mobile_plugin("rollback_to_savepoint", payload)
+      .map_err(Into::into)
+  }
+}
+
+/// The native side decides what it opens and cannot apply capability scopes, so a window whose
+/// scope lists databases is refused instead of being given unrestricted access.
+fn ensure_unscoped(scope: &ConnectScope) -> crate::Result<()> {
+  if scope.allow.is_empty() && scope.deny.is_empty() {
+    Ok(())
+  } else {
+    Err(Error::UnsupportedOption(
+      "connect scopes are not supported on mobile".to_string(),
+    ))
+  }
 }
@@ -1,7 +1,50 @@
+use std::collections::HashMap;
+
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+/// Plugin configuration, read from `plugins > sql-transaction` in `tauri.conf.json`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+  /// Directory relative SQLite paths are resolved against when `connect` does not choose one.
+  #[serde(default)]
+  pub sqlite_base_dir: Option<SqliteBaseDir>,
+  /// Lets relative SQLite paths use `..` to leave their base directory.
+  #[serde(default)]
+  pub allow_path_traversal: bool,
+  /// Databases connected while the plugin is set up.
+  #[serde(default)]
+  pub preload: Vec<PreloadDatabase>,
+  /// SQL statements the frontend can run by name with `execute_named` / `select_named`.
+  #[serde(default)]
+  pub queries: HashMap<String, String>,
+}
+
+/// A database connected at startup, so the frontend only ever sees its alias.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreloadDatabase {
+  pub alias: String,
+  pub url: String,
+  #[serde(default)]
+  pub pool: PoolOptions,
+  #[serde(default)]
+  pub sqlite: SqliteOptions,
+  #[serde(default)]
+  pub numeric: NumericPolicy,
+  #[serde(default)]
+  pub read_only: bool,
+  /// Fail app startup when the database cannot be connected. Defaults to `true`.
+  #[serde(default = "default_true")]
+  pub required: bool,
+}
+
+fn default_true() -> bool {
+  true
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PingRequest {
@@ -14,15 +57,65 @@ pub struct PingResponse {
   pub value: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConnectRequest {
   pub url: String,
+  /// Handle to register the database under instead of a generated one. An alias that is
+  /// already loaded is refused until it is closed.
+  #[serde(default)]
+  pub alias: Option<String>,
+  #[serde(default)]
+  pub pool: PoolOptions,
+  /// Only valid for `sqlite:` URLs.
+  #[serde(default)]
+  pub sqlite: SqliteOptions,
+  /// How selects on this database return numbers JavaScript cannot hold exactly.
+  #[serde(default)]
+  pub numeric: NumericPolicy,
+  /// Reject statements that write through this handle, and open its connections read-only.
+  #[serde(default)]
+  pub read_only: bool,
+}
+
+/// How integers beyond `Number.MAX_SAFE_INTEGER`, MySQL unsigned BIGINTs and DECIMAL /
+/// NUMERIC values appear in select results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NumericPolicy {
+  /// Integers outside the safe range and decimals are strings, so no digit is lost.
+  #[default]
+  Lossless,
+  /// Everything is a JSON number, rounded to the nearest double where needed.
+  Number,
+}
+
+/// Connection pool settings; anything omitted keeps the sqlx default.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolOptions {
+  #[serde(default)]
+  pub max_connections: Option<u32>,
+  #[serde(default)]
+  pub min_connections: Option<u32>,
+  /// How long to wait for a free connection before failing.
+  #[serde(default)]
+  pub acquire_timeout_ms: Option<u64>,
+  /// Closes connections that have been idle for this long; `0` keeps them open.
+  #[serde(default)]
+  pub idle_timeout_ms: Option<u64>,
+  /// Replaces connections once they are this old; `0` keeps them indefinitely.
+  #[serde(default)]
+  pub max_lifetime_ms: Option<u64>,
+  /// Pings a connection before handing it out.
+  #[serde(default)]
+  pub test_before_acquire: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConnectResponse {
+  /// Opaque name for the database in later calls; never contains the URL.
   pub handle: String,
 }
 
@@ -49,6 +142,37 @@ pub struct SelectRequest {
   pub query: String,
   #[serde(default)]
   pub values: Vec<Value>,
+  /// Overrides the database's numeric policy for this query.
+  #[serde(default)]
+  pub numeric: Option<NumericPolicy>,
+}
+
+/// Runs a query registered under `name` on a database or inside a transaction.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteNamedRequest {
+  #[serde(default)]
+  pub db: Option<String>,
+  #[serde(default)]
+  pub tx_id: Option<String>,
+  pub name: String,
+  #[serde(default)]
+  pub values: Vec<Value>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectNamedRequest {
+  #[serde(default)]
+  pub db: Option<String>,
+  #[serde(default)]
+  pub tx_id: Option<String>,
+  pub name: String,
+  #[serde(default)]
+  pub values: Vec<Value>,
+  /// Overrides the database's numeric policy for this query.
+  #[serde(default)]
+  pub numeric: Option<NumericPolicy>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -59,8 +183,179 @@ pub struct SelectResponse {
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
+pub struct BatchStatement {
+  pub query: String,
+  #[serde(default)]
+  pub values: Vec<Value>,
+}
+
+/// Exactly one of `db` (run in a transaction of its own) or `tx_id` (run inside it) must be set.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteBatchRequest {
+  #[serde(default)]
+  pub db: Option<String>,
+  #[serde(default)]
+  pub tx_id: Option<String>,
+  pub statements: Vec<BatchStatement>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteBatchResponse {
+  pub results: Vec<ExecuteResponse>,
+}
+
+/// Reads the first column of the first row as raw bytes, returned without JSON encoding.
+/// Exactly one of `db` or `tx_id` must be set.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectBlobRequest {
+  #[serde(default)]
+  pub db: Option<String>,
+  #[serde(default)]
+  pub tx_id: Option<String>,
+  pub query: String,
+  #[serde(default)]
+  pub values: Vec<Value>,
+}
+
+/// One statement of [`RunTransactionRequest`]. A bind value of the form
+/// `{ "$ref": "/<step>/<path>" }` is replaced by a field of an earlier step's result, e.g.
+/// `/0/lastInsertId` or `/1/rows/0/id` (a JSON pointer into the results array).
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum TransactionStep {
+  Execute {
+    query: String,
+    #[serde(default)]
+    values: Vec<Value>,
+  },
+  Select {
+    query: String,
+    #[serde(default)]
+    values: Vec<Value>,
+  },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunTransactionRequest {
+  #[serde(flatten)]
+  pub begin: BeginTransactionRequest,
+  pub steps: Vec<TransactionStep>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum TransactionStepResult {
+  Execute(ExecuteResponse),
+  Select(SelectResponse),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunTransactionResponse {
+  pub results: Vec<TransactionStepResult>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IsolationLevel {
+  ReadUncommitted,
+  ReadCommitted,
+  RepeatableRead,
+  Serializable,
+}
+
+/// Settings applied to every connection a SQLite pool opens; anything omitted keeps the sqlx default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SqliteOptions {
+  /// Directory a relative path is resolved against; the plugin config default when omitted.
+  #[serde(default)]
+  pub base_dir: Option<SqliteBaseDir>,
+  #[serde(default)]
+  pub journal_mode: Option<SqliteJournalMode>,
+  #[serde(default)]
+  pub synchronous: Option<SqliteSynchronous>,
+  #[serde(default)]
+  pub foreign_keys: Option<bool>,
+  #[serde(default)]
+  pub busy_timeout_ms: Option<u64>,
+  #[serde(default)]
+  pub create_if_missing: Option<bool>,
+  #[serde(default)]
+  pub read_only: Option<bool>,
+  /// Extra `PRAGMA name = value` statements, run in order after the ones above.
+  #[serde(default)]
+  pub pragmas: IndexMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SqliteBaseDir {
+  /// The app config directory.
+  #[default]
+  Config,
+  /// The app data directory.
+  Data,
+  /// The app local data directory.
+  LocalData,
+  /// The app cache directory.
+  Cache,
+  /// Only absolute paths are accepted.
+  AbsoluteOnly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SqliteJournalMode {
+  Delete,
+  Truncate,
+  Persist,
+  Memory,
+  Wal,
+  Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SqliteSynchronous {
+  Off,
+  Normal,
+  Full,
+  Extra,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SqliteLockMode {
+  Deferred,
+  Immediate,
+  Exclusive,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BeginTransactionRequest {
   pub db: String,
+  /// Server default when omitted. Ignored by SQLite, which is always serializable.
+  #[serde(default)]
+  pub isolation_level: Option<IsolationLevel>,
+  /// MySQL and Postgres only.
+  #[serde(default)]
+  pub read_only: bool,
+  /// Postgres only; takes effect for serializable read-only transactions.
+  #[serde(default)]
+  pub deferrable: bool,
+  /// SQLite only; `BEGIN DEFERRED` when omitted. Ignored by other databases.
+  #[serde(default)]
+  pub sqlite_lock_mode: Option<SqliteLockMode>,
+  /// Rolls the transaction back after this long without activity. Falls back to the
+  /// plugin-wide default when omitted; `0` disables the timeout for this transaction.
+  #[serde(default)]
+  pub idle_timeout_ms: Option<u64>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -78,6 +373,18 @@ pub struct TransactionExecuteRequest {
   pub values: Vec<Value>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionSelectRequest {
+  pub tx_id: String,
+  pub query: String,
+  #[serde(default)]
+  pub values: Vec<Value>,
+  /// Overrides the database's numeric policy for this query.
+  #[serde(default)]
+  pub numeric: Option<NumericPolicy>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CommitRequest {
@@ -90,6 +397,43 @@ pub struct RollbackRequest {
   pub tx_id: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseRequest {
+  pub db: String,
+  /// Roll back the transactions still open on this database instead of refusing to close it.
+  #[serde(default)]
+  pub force: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseAllRequest {
+  #[serde(default)]
+  pub force: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavepointRequest {
+  pub tx_id: String,
+  pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseSavepointRequest {
+  pub tx_id: String,
+  pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollbackToSavepointRequest {
+  pub tx_id: String,
+  pub name: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AckResponse {
@@ -0,0 +1,250 @@
+//! Capability scopes limiting which databases `connect` may open.
+//!
+//! A capability grants scoped access with entries like
+//!
+//! ```json
+//! {
+//!   "identifier": "sql-transaction:allow-connect",
+//!   "allow": [{ "schemes": ["sqlite"], "paths": ["$APPDATA/*.db"] }],
+//!   "deny": [{ "paths": ["$APPDATA/secrets.db"] }]
+//! }
+//! ```
+//!
+//! A connection is refused when it matches a `deny` entry, or when there are `allow` entries
+//! and it matches none of them. Without any entries every database may be opened.
+//!
+//! The same scope decides which databases a window may write to: writes are refused with
+//! `readOnly` on a database that a `readOnly` entry admits, or that the scope does not admit
+//! at all, whichever window connected it.
+
+use std::{
+  path::{Component, Path, PathBuf},
+  sync::Arc,
+};
+
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use tauri::{
+  ipc::{CommandScope, GlobalScope},
+  AppHandle, Manager, Runtime, Webview,
+};
+
+use crate::{Error, Result};
+
+/// One `allow` or `deny` entry. Every field that is set must match the connection.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseScope {
+  /// URL schemes, e.g. `["sqlite", "postgres"]`; `postgresql:` URLs count as `postgres`.
+  #[serde(default)]
+  pub schemes: Option<Vec<String>>,
+  /// Glob patterns for the resolved path of a SQLite database or the Unix socket of a server,
+  /// which may start with a path variable such as `$APPDATA`. `*` stays within one
+  /// directory, `**` crosses them.
+  #[serde(default)]
+  pub paths: Option<Vec<String>>,
+  /// Glob patterns for the host of a MySQL or Postgres server, optionally with `:port`.
+  #[serde(default)]
+  pub hosts: Option<Vec<String>>,
+  /// Glob patterns for the alias the database is registered under; a connection without an
+  /// alias never matches.
+  #[serde(default)]
+  pub aliases: Option<Vec<String>>,
+  /// In an `allow` entry: databases it admits are connected read-only, and the window cannot
+  /// write to them through handles opened elsewhere either.
+  #[serde(default)]
+  pub read_only: bool,
+}
+
+/// The database a `connect` call is about to open, kept with the connected database so
+/// later writes can be checked against the caller's scope.
+#[derive(Debug, Clone)]
+pub(crate) struct ScopeTarget {
+  pub scheme: &'static str,
+  /// Resolved SQLite path, `:memory:`, or the Unix socket of a server.
+  pub path: Option<PathBuf>,
+  /// Server host and port, when not connecting through a socket.
+  pub host: Option<(String, u16)>,
+  pub alias: Option<String>,
+}
+
+/// The scope entries that apply to one `connect` call, from the command and the plugin.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConnectScope {
+  pub allow: Vec<Arc<DatabaseScope>>,
+  pub deny: Vec<Arc<DatabaseScope>>,
+}
+
+impl ConnectScope {
+  pub(crate) fn new(command: &CommandScope<DatabaseScope>, global: &GlobalScope<DatabaseScope>) -> Self {
+    Self {
+      allow: command.allows().iter().chain(global.allows()).cloned().collect(),
+      deny: command.denies().iter().chain(global.denies()).cloned().collect(),
+    }
+  }
+
+  /// The scope `webview` would `connect` with, for commands that write to a database. A
+  /// webview that may not call `connect` at all gets an empty scope.
+  pub(crate) fn of_webview<R: Runtime>(webview: &Webview<R>) -> Result<Self> {
+    let resolved = webview
+      .resolve_command_scope::<DatabaseScope>("sql-transaction", "connect")
+      .map_err(|e| Error::NotInScope(format!("failed to resolve the connect scope: {e}")))?;
+    Ok(
+      resolved
+        .map(|scope| Self::new(scope.command_scope(), scope.global_scope()))
+        .unwrap_or_default(),
+    )
+  }
+
+  /// Fails if the target is not allowed, otherwise tells whether it must be connected
+  /// read-only because a matching `allow` entry says so.
+  pub(crate) fn check<R: Runtime>(&self, target: &ScopeTarget, app: &AppHandle<R>) -> Result<bool> {
+    for entry in &self.deny {
+      if entry.matches(target, app)? {
+        return Err(Error::NotInScope(target.describe()));
+      }
+    }
+    if self.allow.is_empty() {
+      return Ok(false);
+    }
+    let mut allowed = false;
+    let mut read_only = false;
+    for entry in &self.allow {
+      if entry.matches(target, app)? {
+        allowed = true;
+        read_only |= entry.read_only;
+      }
+    }
+    if allowed {
+      Ok(read_only)
+    } else {
+      Err(Error::NotInScope(target.describe()))
+    }
+  }
+
+  /// Whether the caller owning this scope may only read `target`: a matching `allow` entry
+  /// is `readOnly`, or the scope does not admit the database at all.
+  pub(crate) fn read_only<R: Runtime>(&self, target: &ScopeTarget, app: &AppHandle<R>) -> Result<bool> {
+    match self.check(target, app) {
+      Err(Error::NotInScope(_)) => Ok(true),
+      result => result,
+    }
+  }
+}
+
+impl DatabaseScope {
+  fn matches<R: Runtime>(&self, target: &ScopeTarget, app: &AppHandle<R>) -> Result<bool> {
+    if let Some(schemes) = &self.schemes {
+      if !schemes.iter().any(|scheme| normalize_scheme(scheme) == target.scheme) {
+        return Ok(false);
+      }
+    }
+    if let Some(paths) = &self.paths {
+      let Some(path) = &target.path else {
+        return Ok(false);
+      };
+      let path = normalize_path(path);
+      if !any_match(paths, |pattern| matches_path(pattern, &path, app))? {
+        return Ok(false);
+      }
+    }
+    if let Some(hosts) = &self.hosts {
+      let Some((host, port)) = &target.host else {
+        return Ok(false);
+      };
+      if !any_match(hosts, |pattern| matches_host(pattern, host, *port))? {
+        return Ok(false);
+      }
+    }
+    if let Some(aliases) = &self.aliases {
+      let Some(alias) = &target.alias else {
+        return Ok(false);
+      };
+      if !any_match(aliases, |pattern| matches_text(pattern, alias))? {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
+}
+
+impl ScopeTarget {
+  fn describe(&self) -> String {
+    match (&self.path, &self.host, &self.alias) {
+      (Some(path), _, _) => path.display().to_string(),
+      (_, Some((host, port)), _) => format!("{}://{host}:{port}", self.scheme),
+      (_, _, Some(alias)) => alias.to_string(),
+      _ => self.scheme.to_string(),
+    }
+  }
+}
+
+fn normalize_scheme(scheme: &str) -> &str {
+  match scheme {
+    "postgresql" => "postgres",
+    scheme => scheme,
+  }
+}
+
+fn any_match(patterns: &[String], matches: impl Fn(&str) -> Result<bool>) -> Result<bool> {
+  for pattern in patterns {
+    if matches(pattern)? {
+      return Ok(true);
+    }
+  }
+  Ok(false)
+}
+
+/// A malformed pattern fails the connection rather than being skipped, so a typo in a `deny`
+/// entry cannot silently open up access.
+fn compile(pattern: &str) -> Result<Pattern> {
+  Pattern::new(pattern).map_err(|e| Error::InvalidRequest(format!("invalid scope pattern {pattern}: {e}")))
+}
+
+fn matches_path<R: Runtime>(pattern: &str, path: &Path, app: &AppHandle<R>) -> Result<bool> {
+  let pattern: PathBuf = if pattern.starts_with('$') {
+    app
+      .path()
+      .parse(pattern)
+      .map_err(|e| Error::InvalidRequest(format!("invalid scope pattern {pattern}: {e}")))?
+  } else {
+    PathBuf::from(pattern)
+  };
+  let options = MatchOptions {
+    require_literal_separator: true,
+    ..Default::default()
+  };
+  Ok(compile(&pattern.to_string_lossy())?.matches_path_with(path, options))
+}
+
+/// Resolves `.` and `..` lexically, so `$APPDATA/../other.db` cannot pass for a file
+/// inside `$APPDATA`.
+fn normalize_path(path: &Path) -> PathBuf {
+  let mut normalized = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir => {
+        normalized.pop();
+      }
+      component => normalized.push(component),
+    }
+  }
+  normalized
+}
+
+fn matches_host(pattern: &str, host: &str, port: u16) -> Result<bool> {
+  // `[::1]` has colons of its own, so only a trailing run of digits is a port.
+  match pattern.rsplit_once(':') {
+    Some((pattern_host, pattern_port)) if pattern_port.parse::<u16>().is_ok() => {
+      Ok(pattern_port.parse() == Ok(port) && matches_text(pattern_host, host)?)
+    }
+    _ => matches_text(pattern, host),
+  }
+}
+
+/// Exact names are compared as they are, so an IPv6 host like `[::1]` is not read as a
+/// character class.
+fn matches_text(pattern: &str, text: &str) -> Result<bool> {
+  Ok(pattern == text || compile(pattern)?.matches(text))
+}